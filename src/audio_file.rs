@@ -0,0 +1,414 @@
+use std::{
+    fs::File,
+    io::{BufReader, Chain, Cursor, Read, Seek, SeekFrom},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail};
+
+use crate::WavReader;
+
+/// How far before the wanted position a seek aims, so the decoder has
+/// settled by the time it gets there.
+const SEEK_MARGIN: u64 = 8192;
+
+/// Decoded but not yet read frames of a compressed stream.
+#[derive(Default)]
+struct Pending {
+    samples: Vec<f32>,
+    start: usize,
+}
+
+impl Pending {
+    fn frames(&self, channels: usize) -> usize {
+        (self.samples.len() - self.start) / channels
+    }
+
+    fn clear(&mut self) {
+        self.samples.clear();
+        self.start = 0;
+    }
+}
+
+/// A compressed stream decoded a block or packet at a time.
+trait Decoder {
+    /// Decode the next block into the pending frames, returning false at
+    /// the end of the stream.
+    fn decode(&mut self) -> anyhow::Result<bool>;
+    fn state(&mut self) -> (&mut Pending, &mut usize);
+}
+
+fn read_decoded(decoder: &mut impl Decoder, channels: usize, output: &mut Vec<f32>, frames: usize)
+        -> anyhow::Result<usize> {
+    let mut read = 0;
+    while read < frames {
+        let (pending, position) = decoder.state();
+        let available = pending.frames(channels);
+        if available == 0 {
+            if !decoder.decode()? {
+                break
+            }
+            continue
+        }
+
+        let count = available.min(frames - read);
+        let end = pending.start + count * channels;
+        output.extend_from_slice(&pending.samples[pending.start..end]);
+        pending.start = end;
+        *position += count;
+        read += count;
+    }
+    Ok(read)
+}
+
+/// The stream marker and a STREAMINFO block marked as the last metadata
+/// block, which is enough for claxon to start decoding at any frame.
+const FLAC_HEADER_LEN: usize = 42;
+/// Bytes searched for a frame header after a guessed seek offset.
+const FLAC_SYNC_WINDOW: u64 = 1 << 18;
+/// Times a seek backs off further when it landed past the wanted frame.
+const FLAC_SEEK_TRIES: u32 = 8;
+
+type FlacInput = Chain<Cursor<[u8; FLAC_HEADER_LEN]>, File>;
+
+pub struct FlacStream {
+    path: PathBuf,
+    header: [u8; FLAC_HEADER_LEN],
+    /// Byte range of the audio frames in the file.
+    audio: Range<u64>,
+    reader: claxon::FlacReader<FlacInput>,
+    info: claxon::metadata::StreamInfo,
+    block: Vec<i32>,
+    pending: Pending,
+    position: usize,
+}
+
+impl FlacStream {
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut header = [0; FLAC_HEADER_LEN];
+        file.read_exact(&mut header)?;
+        // STREAMINFO always comes first and is 34 bytes long
+        if &header[..4] != b"fLaC" || header[4] & 0x7F != 0 || header[5..8] != [0, 0, 34] {
+            bail!("not a FLAC file")
+        }
+
+        // skip the other metadata blocks
+        let mut start = 4;
+        loop {
+            let mut block = [0; 4];
+            file.seek(SeekFrom::Start(start))?;
+            file.read_exact(&mut block)?;
+            start += 4 + u32::from_be_bytes([0, block[1], block[2], block[3]]) as u64;
+            if block[0] & 0x80 != 0 {
+                break
+            }
+        }
+        header[4] |= 0x80;
+        let audio = start..file.seek(SeekFrom::End(0))?;
+
+        let reader = Self::reader_at(path, header, audio.start)?;
+        let info = reader.streaminfo();
+        if info.channels == 0 {
+            bail!("FLAC file has no channels")
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            header,
+            audio,
+            reader,
+            info,
+            block: Vec::new(),
+            pending: Pending::default(),
+            position: 0,
+        })
+    }
+
+    /// A reader decoding the file from the frame starting at `offset`.
+    fn reader_at(path: &Path, header: [u8; FLAC_HEADER_LEN], offset: u64)
+            -> anyhow::Result<claxon::FlacReader<FlacInput>> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(claxon::FlacReader::new(Cursor::new(header).chain(file))?)
+    }
+
+    fn rewind(&mut self) -> anyhow::Result<()> {
+        self.reader = Self::reader_at(&self.path, self.header, self.audio.start)?;
+        self.pending.clear();
+        self.position = 0;
+        Ok(())
+    }
+
+    /// Jump to a frame header before `frame`, guessing its offset from the
+    /// average bitrate. Returns false if every guess landed past `frame`.
+    fn seek_frame(&mut self, frame: usize) -> anyhow::Result<bool> {
+        let Some(total) = self.info.samples.filter(|samples| *samples != 0) else {
+            return Ok(false)
+        };
+
+        let length = (self.audio.end - self.audio.start) as f64;
+        let mut target = (frame as u64).saturating_sub(SEEK_MARGIN);
+        for attempt in 0..FLAC_SEEK_TRIES {
+            let offset = self.audio.start + (length * target as f64 / total as f64) as u64;
+            if self.sync(offset)?.is_some_and(|time| time <= frame) {
+                return Ok(true)
+            }
+            if target == 0 {
+                break
+            }
+            target = target.saturating_sub(SEEK_MARGIN << attempt);
+        }
+        Ok(false)
+    }
+
+    /// Continue decoding at the first frame header after `offset`,
+    /// returning the position of that frame. Anything looking like a sync
+    /// code is tried, claxon checks the CRCs of the header and frame.
+    fn sync(&mut self, offset: u64) -> anyhow::Result<Option<usize>> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut window = Vec::new();
+        file.take(FLAC_SYNC_WINDOW).read_to_end(&mut window)?;
+
+        for (i, bytes) in window.windows(2).enumerate() {
+            if bytes[0] != 0xFF || bytes[1] & 0xFE != 0xF8 {
+                continue
+            }
+            let Ok(mut reader) = Self::reader_at(&self.path, self.header, offset + i as u64) else {
+                continue
+            };
+            let buffer = std::mem::take(&mut self.block);
+            if let Ok(Some(block)) = reader.blocks().read_next_or_eof(buffer) {
+                let time = self.block_time(&block);
+                self.reader = reader;
+                self.fill(block);
+                self.position = time;
+                return Ok(Some(time))
+            }
+        }
+        Ok(None)
+    }
+
+    /// claxon derives the time of fixed size blocks from their own size,
+    /// which is off for the shorter last block.
+    fn block_time(&self, block: &claxon::Block) -> usize {
+        let fixed = self.info.min_block_size == self.info.max_block_size;
+        match self.info.samples {
+            Some(total) if fixed && block.duration() < self.info.max_block_size as u32 =>
+                total.saturating_sub(block.duration() as u64) as usize,
+            _ => block.time() as usize,
+        }
+    }
+
+    fn fill(&mut self, block: claxon::Block) {
+        let scale = 1.0 / (1_u64 << (self.info.bits_per_sample - 1)) as f32;
+        self.pending.clear();
+        for i in 0..block.duration() {
+            for channel in 0..block.channels() {
+                self.pending.samples.push(block.sample(channel, i) as f32 * scale);
+            }
+        }
+        self.block = block.into_buffer();
+    }
+}
+
+impl Decoder for FlacStream {
+    fn decode(&mut self) -> anyhow::Result<bool> {
+        let buffer = std::mem::take(&mut self.block);
+        let Some(block) = self.reader.blocks().read_next_or_eof(buffer)? else {
+            return Ok(false)
+        };
+        self.fill(block);
+        Ok(true)
+    }
+
+    fn state(&mut self) -> (&mut Pending, &mut usize) {
+        (&mut self.pending, &mut self.position)
+    }
+}
+
+pub struct OggStream {
+    path: PathBuf,
+    reader: lewton::inside_ogg::OggStreamReader<BufReader<File>>,
+    frames: usize,
+    pending: Pending,
+    position: usize,
+}
+
+impl OggStream {
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        let frames = ogg_length(path)?;
+        let reader = lewton::inside_ogg::OggStreamReader::new(BufReader::new(File::open(path)?))?;
+        if reader.ident_hdr.audio_channels == 0 {
+            bail!("Ogg file has no channels")
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            reader,
+            frames,
+            pending: Pending::default(),
+            position: 0,
+        })
+    }
+
+    fn channels(&self) -> usize {
+        self.reader.ident_hdr.audio_channels as usize
+    }
+
+    fn rewind(&mut self) -> anyhow::Result<()> {
+        self.reader = lewton::inside_ogg::OggStreamReader::new(BufReader::new(File::open(&self.path)?))?;
+        self.pending.clear();
+        self.position = 0;
+        Ok(())
+    }
+
+    /// Jump to the page before `frame`. The position is only known again
+    /// once a packet ending a page has been decoded, returns false if that
+    /// already lies past `frame`.
+    fn seek_page(&mut self, frame: usize) -> anyhow::Result<bool> {
+        self.reader.seek_absgp_pg((frame as u64).saturating_sub(SEEK_MARGIN))?;
+        self.pending.clear();
+        loop {
+            if !self.decode()? {
+                self.position = self.frames;
+                return Ok(self.frames <= frame)
+            }
+            if let Some(granule) = self.reader.get_last_absgp() {
+                self.pending.clear();
+                self.position = granule as usize;
+                return Ok(self.position <= frame)
+            }
+        }
+    }
+}
+
+impl Decoder for OggStream {
+    fn decode(&mut self) -> anyhow::Result<bool> {
+        let packet: Option<lewton::samples::InterleavedSamples<f32>> =
+            self.reader.read_dec_packet_generic()?;
+        let Some(packet) = packet else {
+            return Ok(false)
+        };
+        self.pending.clear();
+        self.pending.samples.extend(packet.samples);
+        Ok(true)
+    }
+
+    fn state(&mut self) -> (&mut Pending, &mut usize) {
+        (&mut self.pending, &mut self.position)
+    }
+}
+
+/// Length in frames of an Ogg Vorbis file, from the granule position of its
+/// last page.
+fn ogg_length(path: &Path) -> anyhow::Result<usize> {
+    const TAIL: u64 = 1 << 16;
+
+    let mut file = File::open(path)?;
+    let len = file.seek(SeekFrom::End(0))?;
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL)))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+
+    let page = tail.windows(4).rposition(|bytes| bytes == b"OggS")
+        .filter(|page| page + 14 <= tail.len())
+        .ok_or_else(|| anyhow!("no Ogg page found"))?;
+    Ok(u64::from_le_bytes(tail[page + 6..page + 14].try_into().unwrap()) as usize)
+}
+
+/// Streaming reader for WAV, FLAC and Ogg Vorbis files, decoding to f32.
+/// The format is picked by the file extension.
+pub enum AudioFileReader {
+    Wav(WavReader),
+    Flac(Box<FlacStream>),
+    Ogg(Box<OggStream>),
+}
+
+impl AudioFileReader {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|ext| ext.to_str())
+            .unwrap_or("").to_ascii_lowercase();
+        match extension.as_str() {
+            "wav" => Ok(Self::Wav(WavReader::open(path)?)),
+            "flac" => Ok(Self::Flac(Box::new(FlacStream::open(path)?))),
+            "ogg" | "oga" => Ok(Self::Ogg(Box::new(OggStream::open(path)?))),
+            _ => bail!("unsupported audio file type: '{extension}'"),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        match self {
+            Self::Wav(reader) => reader.spec.sample_rate,
+            Self::Flac(stream) => stream.info.sample_rate,
+            Self::Ogg(stream) => stream.reader.ident_hdr.audio_sample_rate,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        match self {
+            Self::Wav(reader) => reader.spec.channels as usize,
+            Self::Flac(stream) => stream.info.channels as usize,
+            Self::Ogg(stream) => stream.channels(),
+        }
+    }
+
+    /// Length in frames (one sample per channel).
+    pub fn frames(&self) -> usize {
+        match self {
+            Self::Wav(reader) => reader.frames(),
+            Self::Flac(stream) => stream.info.samples.unwrap_or(0) as usize,
+            Self::Ogg(stream) => stream.frames,
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        match self {
+            Self::Wav(reader) => reader.position(),
+            Self::Flac(stream) => stream.position,
+            Self::Ogg(stream) => stream.position,
+        }
+    }
+
+    pub fn seek(&mut self, frame: usize) -> anyhow::Result<()> {
+        match self {
+            Self::Wav(reader) => return reader.seek(frame),
+            Self::Flac(stream) => {
+                let far = SEEK_MARGIN as usize * 2 < frame.saturating_sub(stream.position);
+                if (frame < stream.position || far) && !stream.seek_frame(frame)? {
+                    // landed past the frame, start over
+                    stream.rewind()?;
+                }
+            }
+            Self::Ogg(stream) => {
+                let far = SEEK_MARGIN as usize * 2 < frame.saturating_sub(stream.position);
+                if (frame < stream.position || far) && !stream.seek_page(frame)? {
+                    // landed past the frame, start over
+                    stream.rewind()?;
+                }
+            }
+        }
+
+        // decode up to the frame
+        let mut skipped = Vec::new();
+        while self.position() < frame {
+            skipped.clear();
+            if self.read_frames(&mut skipped, frame - self.position())? == 0 {
+                break
+            }
+        }
+        Ok(())
+    }
+
+    /// Read up to `frames` frames of interleaved samples into `output`,
+    /// returning the amount of frames read.
+    pub fn read_frames(&mut self, output: &mut Vec<f32>, frames: usize) -> anyhow::Result<usize> {
+        let channels = self.channels();
+        match self {
+            Self::Wav(reader) => reader.read_frames(output, frames),
+            Self::Flac(stream) => read_decoded(&mut **stream, channels, output, frames),
+            Self::Ogg(stream) => read_decoded(&mut **stream, channels, output, frames),
+        }
+    }
+}
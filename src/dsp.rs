@@ -0,0 +1,433 @@
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use serde::{Serialize, Deserialize};
+
+/// Second-order IIR filter (transposed direct form II).
+#[derive(Clone, Copy, Default)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    /// Build a filter from raw coefficients, normalized by `a0`.
+    pub fn from_coefficients(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    pub fn lowpass(freq: f32, q: f32, sample_rate: f32) -> Self {
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::from_coefficients(
+            (1.0 - cos) / 2.0,
+            1.0 - cos,
+            (1.0 - cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    pub fn highpass(freq: f32, q: f32, sample_rate: f32) -> Self {
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::from_coefficients(
+            (1.0 + cos) / 2.0,
+            -(1.0 + cos),
+            (1.0 + cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    pub fn bandpass(freq: f32, q: f32, sample_rate: f32) -> Self {
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::from_coefficients(
+            alpha,
+            0.0,
+            -alpha,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    pub fn peak(freq: f32, q: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let a = 10.0_f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::from_coefficients(
+            1.0 + alpha * a,
+            -2.0 * cos,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos,
+            1.0 - alpha / a,
+        )
+    }
+
+    pub fn low_shelf(freq: f32, q: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let a = 10.0_f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let sqrt = 2.0 * a.sqrt() * alpha;
+        Self::from_coefficients(
+            a * ((a + 1.0) - (a - 1.0) * cos + sqrt),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            a * ((a + 1.0) - (a - 1.0) * cos - sqrt),
+            (a + 1.0) + (a - 1.0) * cos + sqrt,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+            (a + 1.0) + (a - 1.0) * cos - sqrt,
+        )
+    }
+
+    pub fn high_shelf(freq: f32, q: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let a = 10.0_f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let sqrt = 2.0 * a.sqrt() * alpha;
+        Self::from_coefficients(
+            a * ((a + 1.0) + (a - 1.0) * cos + sqrt),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - sqrt),
+            (a + 1.0) - (a - 1.0) * cos + sqrt,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - sqrt,
+        )
+    }
+
+    /// Replace the coefficients with those of `other` while keeping the
+    /// filter state, so parameters can change without clicks.
+    pub fn set_coefficients(&mut self, other: Self) {
+        *self = Self {
+            z1: self.z1,
+            z2: self.z2,
+            ..other
+        };
+    }
+
+    /// Magnitude response at `freq` in decibels.
+    pub fn response_db(&self, freq: f32, sample_rate: f32) -> f32 {
+        let w = 2.0 * PI * freq / sample_rate;
+        let (sin1, cos1) = w.sin_cos();
+        let (sin2, cos2) = (2.0 * w).sin_cos();
+
+        let num_re = self.b0 + self.b1 * cos1 + self.b2 * cos2;
+        let num_im = -(self.b1 * sin1 + self.b2 * sin2);
+        let den_re = 1.0 + self.a1 * cos1 + self.a2 * cos2;
+        let den_im = -(self.a1 * sin1 + self.a2 * sin2);
+
+        let num = num_re * num_re + num_im * num_im;
+        let den = den_re * den_re + den_im * den_im;
+        10.0 * (num / den).log10()
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+/// Circular buffer delay with fractional (linearly interpolated) reads.
+#[derive(Clone, Default)]
+pub struct DelayLine {
+    buffer: Vec<f32>,
+    index: usize,
+}
+
+impl DelayLine {
+    pub fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length.max(1)],
+            index: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub fn push(&mut self, value: f32) {
+        self.buffer[self.index] = value;
+        self.index = (self.index + 1) % self.buffer.len();
+    }
+
+    /// Read the value pushed `delay` samples ago, where 0 is the latest one.
+    /// The longest delay is two samples less than the length, so the
+    /// interpolation doesn't wrap around to the latest sample.
+    pub fn read(&self, delay: f32) -> f32 {
+        let len = self.buffer.len();
+        if len < 2 {
+            return self.buffer.first().copied().unwrap_or(0.0)
+        }
+        let delay = delay.clamp(0.0, (len - 2) as f32);
+        let whole = delay as usize;
+        let frac = delay - whole as f32;
+
+        let a = self.buffer[(self.index + len * 2 - 1 - whole) % len];
+        let b = self.buffer[(self.index + len * 2 - 2 - whole) % len];
+        a + (b - a) * frac
+    }
+}
+
+/// In-place iterative radix-2 FFT. Both slices must have the same
+/// power of two length.
+pub fn fft(re: &mut [f32], im: &mut [f32]) {
+    let len = re.len();
+    assert!(len.is_power_of_two() && im.len() == len);
+
+    // bit reversal permutation
+    let bits = len.trailing_zeros();
+    for i in 0..len {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= len {
+        let angle = -2.0 * PI / size as f32;
+        for start in (0..len).step_by(size) {
+            for k in 0..size / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let a = start + k;
+                let b = a + size / 2;
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        size *= 2;
+    }
+}
+
+pub fn hann(index: usize, len: usize) -> f32 {
+    0.5 - 0.5 * (2.0 * PI * index as f32 / len as f32).cos()
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl Window {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Rectangular => "rectangular",
+            Self::Hann => "hann",
+            Self::Hamming => "hamming",
+            Self::Blackman => "blackman",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        use Window::*;
+        match self {
+            Rectangular => Hann,
+            Hann => Hamming,
+            Hamming => Blackman,
+            Blackman => Rectangular,
+        }
+    }
+
+    pub fn value(&self, index: usize, len: usize) -> f32 {
+        let phase = 2.0 * PI * index as f32 / len as f32;
+        match self {
+            Self::Rectangular => 1.0,
+            Self::Hann => hann(index, len),
+            Self::Hamming => 0.54 - 0.46 * phase.cos(),
+            Self::Blackman => 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos(),
+        }
+    }
+}
+
+/// Hann windowed magnitude spectrum of `samples` (length must be a power of
+/// two), returning `samples.len() / 2` bins normalized so a full scale sine
+/// peaks around 1.0.
+pub fn magnitude_spectrum(samples: &[f32]) -> Vec<f32> {
+    windowed_spectrum(samples, Window::Hann)
+}
+
+/// Like `magnitude_spectrum` with a choice of window. The bins are divided
+/// by the window's gain, so the level of a sine doesn't depend on it.
+pub fn windowed_spectrum(samples: &[f32], window: Window) -> Vec<f32> {
+    let len = samples.len();
+    let mut gain = 0.0;
+    let mut re: Vec<f32> = samples.iter().enumerate()
+        .map(|(i, sample)| {
+            let value = window.value(i, len);
+            gain += value;
+            sample * value
+        })
+        .collect();
+    let mut im = vec![0.0; len];
+    fft(&mut re, &mut im);
+
+    (0..len / 2)
+        .map(|i| (re[i] * re[i] + im[i] * im[i]).sqrt() * 2.0 / gain)
+        .collect()
+}
+
+/// Estimate the fundamental frequency of `samples` with the YIN algorithm.
+/// Periods up to half the length of `samples` are searched, `threshold`
+/// (around 0.1 to 0.2) trades missed detections for octave errors.
+/// `difference` is scratch space, keeping it between calls avoids
+/// allocating in the audio callback.
+pub fn yin(samples: &[f32], difference: &mut Vec<f32>, sample_rate: f32, threshold: f32) -> Option<f32> {
+    let max_tau = samples.len() / 2;
+    if max_tau < 3 {
+        return None
+    }
+
+    // cumulative mean normalized difference function
+    difference.clear();
+    difference.resize(max_tau, 1.0);
+    let mut sum = 0.0;
+    for tau in 1..max_tau {
+        let value: f32 = (0..max_tau)
+            .map(|i| {
+                let delta = samples[i] - samples[i + tau];
+                delta * delta
+            })
+            .sum();
+        sum += value;
+        difference[tau] = if sum == 0.0 { 1.0 } else { value * tau as f32 / sum };
+    }
+
+    // the first dip below the threshold, followed down to its minimum
+    let mut tau = (2..max_tau).find(|tau| difference[*tau] < threshold)?;
+    while tau + 1 < max_tau && difference[tau + 1] < difference[tau] {
+        tau += 1;
+    }
+
+    // parabolic interpolation between the neighbouring lags
+    let offset = if tau + 1 < max_tau {
+        let (a, b, c) = (difference[tau - 1], difference[tau], difference[tau + 1]);
+        let denominator = a - 2.0 * b + c;
+        if denominator == 0.0 { 0.0 } else { 0.5 * (a - c) / denominator }
+    } else {
+        0.0
+    };
+
+    Some(sample_rate / (tau as f32 + offset))
+}
+
+/// Small xorshift PRNG, good enough for noise and not tied to the OS so
+/// sequences can be reproduced from a seed.
+#[derive(Clone, Copy)]
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        // the state must never be zero
+        Self { state: seed.wrapping_mul(2654435761) | 1 }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// Uniform value in -1.0..1.0
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 23) as f32 - 1.0
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+/// Fixed size single producer single consumer queue of samples. Pushing
+/// never allocates or blocks, and one thread may push while another pops.
+pub struct RingBuffer {
+    buffer: Box<[AtomicU32]>,
+    read: AtomicUsize,
+    write: AtomicUsize,
+}
+
+impl RingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            // one slot is always kept free to tell a full buffer from an empty one
+            buffer: (0..capacity + 1).map(|_| AtomicU32::new(0)).collect(),
+            read: AtomicUsize::new(0),
+            write: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns false if the buffer is full and the value was dropped.
+    pub fn push(&self, value: f32) -> bool {
+        let write = self.write.load(Ordering::Relaxed);
+        let next = (write + 1) % self.buffer.len();
+        if next == self.read.load(Ordering::Acquire) {
+            return false
+        }
+        self.buffer[write].store(value.to_bits(), Ordering::Relaxed);
+        self.write.store(next, Ordering::Release);
+        true
+    }
+
+    pub fn pop(&self) -> Option<f32> {
+        let read = self.read.load(Ordering::Relaxed);
+        if read == self.write.load(Ordering::Acquire) {
+            return None
+        }
+        let value = f32::from_bits(self.buffer[read].load(Ordering::Relaxed));
+        self.read.store((read + 1) % self.buffer.len(), Ordering::Release);
+        Some(value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read.load(Ordering::Acquire) == self.write.load(Ordering::Acquire)
+    }
+}
@@ -28,6 +28,11 @@ const DEFAULT_WIN_SIZE: u32 = 160;
 const WIN_PADDING: u8 = 20;
 const WIN_PADDING_TOP: u8 = 10; // extra top padding
 
+const METER_MARGIN: u32 = 6;
+const METER_HEIGHT: u32 = 8;
+const METER_MIN_DB: f32 = -60.0;
+const METER_MAX_DB: f32 = 6.0;
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ModuleWindow {
     // TODO change to i16?
@@ -37,6 +42,8 @@ pub struct ModuleWindow {
     pub height: u32,
     title: Box<str>,
     inputs: Vec<(DataType, Box<str>)>,
+    #[serde(skip)]
+    extra_outputs: usize,
 }
 
 impl ModuleWindow {
@@ -48,6 +55,7 @@ impl ModuleWindow {
             height: DEFAULT_WIN_SIZE,
             title: title.into(),
             inputs: Vec::new(),
+            extra_outputs: 0,
         }
     }
 
@@ -74,17 +82,20 @@ impl ModuleWindow {
         )
     }
 
-    fn output_conn(&self) -> (i32, i32) {
-        let (_, height) = self.padded_size();
-        (self.x + self.padded_size().0 as i32, self.y + height as i32 / 2)
+    fn output_conns(&self) -> Vec<(i32, i32)> {
+        let (width, _) = self.padded_size();
+        Self::spaced_conns(self.x + width as i32, self.y, self.padded_size().1, 1 + self.extra_outputs)
     }
 
     fn input_conns(&self) -> Vec<(i32, i32)> {
+        Self::spaced_conns(self.x, self.y, self.padded_size().1, self.inputs.len())
+    }
+
+    fn spaced_conns(x: i32, y: i32, height: u32, len: usize) -> Vec<(i32, i32)> {
         const SPACING: i32 = 20;
-        let (_, height) = self.padded_size();
-        let len = self.inputs.len() as i32;
-        let y = self.y + height as i32 / 2 - SPACING * (len / 2);
-        (0..len).map(|i| (self.x, y + SPACING * i as i32)).collect()
+        let len = len as i32;
+        let y = y + height as i32 / 2 - SPACING * (len / 2);
+        (0..len).map(|i| (x, y + SPACING * i)).collect()
     }
 }
 
@@ -99,7 +110,7 @@ pub struct Gui {
 #[derive(Clone, Debug)]
 enum Selection {
     Window(ModuleId),
-    Output(ModuleId),
+    Output(ModuleId, usize),
     Input(ModuleId, usize),
 }
 
@@ -140,11 +151,11 @@ impl Gui {
 
         for (id, module) in self.modules.iter().rev() {
             let id = *id;
-            let (cx, cy) = module.output_conn();
-
-            if cx - SEL < x && x < cx + SEL && cy - SEL < y && y < cy + SEL {
-                // self.selected = id;
-                return Some(Selection::Output(id));
+            for (conn_id, (cx, cy)) in module.output_conns().iter().enumerate() {
+                if cx - SEL < x && x < cx + SEL && cy - SEL < y && y < cy + SEL {
+                    // self.selected = id;
+                    return Some(Selection::Output(id, conn_id));
+                }
             }
 
             for (conn_id, (cx, cy)) in module.input_conns().iter().enumerate() {
@@ -235,18 +246,18 @@ impl Gui {
                             let new_selection = self.check_selected(x, y);
 
                             match new_selection {
-                                Some(Selection::Output(out_id)) => {
+                                Some(Selection::Output(out_id, out_conn_id)) => {
                                     match selection {
                                         Some(Selection::Input(in_id, conn_id)) => {
-                                            app.connect(out_id, (in_id, conn_id));
+                                            app.connect((out_id, out_conn_id), (in_id, conn_id));
                                         }
                                         _ => {}
                                     }
                                 }
                                 Some(Selection::Input(in_id, conn_id)) => {
                                     match selection {
-                                        Some(Selection::Output(out_id)) => {
-                                            app.connect(out_id, (in_id, conn_id));
+                                        Some(Selection::Output(out_id, out_conn_id)) => {
+                                            app.connect((out_id, out_conn_id), (in_id, conn_id));
                                         }
                                         _ => {}
                                     }
@@ -289,8 +300,8 @@ impl Gui {
             let mut pending_conn_line: Option<((i32, i32), (i32, i32))> = None;
 
             match selection {
-                Some(Selection::Output(mod_id)) => {
-                    let output_conn = self.module(mod_id).output_conn();
+                Some(Selection::Output(mod_id, conn_id)) => {
+                    let output_conn = self.module(mod_id).output_conns()[conn_id];
                     let output_conn = (output_conn.0 + self.x, output_conn.1 + self.y);
                     pending_conn_line = Some((output_conn, (mouse.x(), mouse.y())));
                 }
@@ -304,6 +315,8 @@ impl Gui {
 
             // draw windows / modules
             for (id, module_win) in self.modules.iter_mut() {
+                let mut app = app.lock().unwrap();
+
                 // draw the window
 
                 let (width, height) = module_win.padded_size();
@@ -323,6 +336,16 @@ impl Gui {
                     rendered_title.height()
                 )).unwrap();
 
+                let audio = *id == 0 || matches!(app.modules[id].get_output_type(), DataType::Audio);
+                if audio && let Some(level) = app.levels.get(id) {
+                    draw_level_meter(&mut mod_canvas, Rect::new(
+                        WIN_PADDING as i32,
+                        (height - WIN_PADDING as u32 + METER_MARGIN) as i32,
+                        width - WIN_PADDING as u32 * 2,
+                        METER_HEIGHT,
+                    ), level);
+                }
+
                 mod_canvas.set_draw_color(if *id == self.selected {
                     COLOR_BORDER_SEL
                 } else {
@@ -345,48 +368,50 @@ impl Gui {
                     ).unwrap();
                 }
 
-                if *id != 0 {
-                    // draw output connections
-                    let output = module_win.output_conn();
-                    canvas.filled_circle(
-                        (output.0 + self.x) as i16,
-                        (output.1 + self.y) as i16,
-                        5, COLOR_CONN
-                    ).unwrap();
+                let interact = if self.selected == *id && selection.is_none() {
+                    let x = mouse.x() - self.x - module_win.x - WIN_PADDING as i32;
+                    let y = mouse.y() - self.y - module_win.y - WIN_PADDING as i32 - WIN_PADDING_TOP as i32;
 
-                    let mut app = app.lock().unwrap();
+                    if 0 <= x && x < module_win.width as i32
+                        && 0 <= y && y < module_win.height as i32 {
 
-                    // do Module::draw
+                        Some(ModuleInteractInfo {
+                            x: x as u16,
+                            y: y as u16,
+                            click: clicked_mouse_btn,
+                            event_pump: &event_pump,
+                        })
+
+                    } else { None }
+                } else { None };
 
-                    let interact = if self.selected == *id && selection.is_none() {
-                        let x = mouse.x() - self.x - module_win.x - WIN_PADDING as i32;
-                        let y = mouse.y() - self.y - module_win.y - WIN_PADDING as i32 - WIN_PADDING_TOP as i32;
-
-                        if 0 <= x && x < module_win.width as i32
-                            && 0 <= y && y < module_win.height as i32 {
-
-                            Some(ModuleInteractInfo {
-                                x: x as u16,
-                                y: y as u16,
-                                click: clicked_mouse_btn,
-                                event_pump: &event_pump,
-                            })
-
-                        } else { None }
-                    } else { None };
-
-                    if let Some(surface) = app.module(*id).draw(&ui_context, interact) {
-                        let texture = surface.as_texture(&texture_creator).unwrap();
-                        canvas.copy(
-                            &texture,
-                            surface.rect(),
-                            module_win.rect()
-                                .right_shifted(WIN_PADDING as i32 + self.x)
-                                .bottom_shifted(WIN_PADDING as i32 + WIN_PADDING_TOP as i32 + self.y)
+                let surface = if *id != 0 {
+                    // draw output connections
+                    for output in module_win.output_conns() {
+                        canvas.filled_circle(
+                            (output.0 + self.x) as i16,
+                            (output.1 + self.y) as i16,
+                            5, COLOR_CONN
                         ).unwrap();
-                        module_win.width = surface.rect().width();
-                        module_win.height = surface.rect().height();
                     }
+
+                    // do Module::draw
+                    app.module(*id).draw(&ui_context, interact)
+                } else {
+                    draw_loudness(&ui_context, interact, &mut app.loudness)
+                };
+
+                if let Some(surface) = surface {
+                    let texture = surface.as_texture(&texture_creator).unwrap();
+                    canvas.copy(
+                        &texture,
+                        surface.rect(),
+                        module_win.rect()
+                            .right_shifted(WIN_PADDING as i32 + self.x)
+                            .bottom_shifted(WIN_PADDING as i32 + WIN_PADDING_TOP as i32 + self.y)
+                    ).unwrap();
+                    module_win.width = surface.rect().width();
+                    module_win.height = surface.rect().height();
                 }
             }
 
@@ -402,15 +427,18 @@ impl Gui {
 
                 // TODO update this less often
                 for (i, module) in app.modules.iter() {
-                    self.module_mut(*i).inputs =
+                    let module_win = self.module_mut(*i);
+                    module_win.inputs =
                         module.get_inputs().iter().map(|i| (i.0.clone(), i.1.into())).collect();
+                    module_win.extra_outputs = module.get_extra_outputs().len();
                 }
 
                 canvas.set_draw_color(COLOR_CONN);
-                for ((input_id, conn_id), output_id) in &app.conns {
+                for ((input_id, conn_id), (output_id, output_conn_id)) in &app.conns {
                     let inputs = self.module(*input_id).input_conns();
                     let input = inputs[*conn_id];
-                    let output = self.module(*output_id).output_conn();
+                    let Some(&output) = self.module(*output_id).output_conns().get(*output_conn_id)
+                        else { continue };
                     canvas.draw_line(
                         (input.0 + self.x, input.1 + self.y),
                         (output.0 + self.x, output.1 + self.y)
@@ -423,3 +451,62 @@ impl Gui {
         }
     }
 }
+
+/// Horizontal meter with the RMS level as a bar and the peak level as a
+/// line, from `METER_MIN_DB` to `METER_MAX_DB`.
+fn draw_level_meter(canvas: &mut sdl2::render::SurfaceCanvas, rect: Rect, meter: &LevelMeter) {
+    let to_x = |amplitude: f32| {
+        let db = amplitude_to_db(amplitude).clamp(METER_MIN_DB, METER_MAX_DB);
+        rect.x() + ((db - METER_MIN_DB) / (METER_MAX_DB - METER_MIN_DB) * rect.width() as f32) as i32
+    };
+
+    canvas.set_draw_color(Color::RGB(60, 60, 60));
+    canvas.fill_rect(rect).unwrap();
+
+    let rms = to_x(meter.rms());
+    if rect.x() < rms {
+        canvas.set_draw_color(Color::RGB(0, 180, 0));
+        canvas.fill_rect(Rect::new(rect.x(), rect.y(), (rms - rect.x()) as u32, rect.height())).unwrap();
+    }
+
+    // clipping
+    canvas.set_draw_color(if 1.0 < meter.peak() {
+        Color::RGB(255, 0, 0)
+    } else {
+        Color::RGB(255, 220, 0)
+    });
+    let peak = to_x(meter.peak());
+    canvas.draw_line((peak, rect.y()), (peak, rect.bottom() - 1)).unwrap();
+
+    let zero = to_x(1.0);
+    canvas.set_draw_color(Color::RGB(200, 200, 200));
+    canvas.draw_line((zero, rect.y()), (zero, rect.bottom() - 1)).unwrap();
+}
+
+/// Contents of the Output window: loudness of the whole patch.
+fn draw_loudness(ui: &UiContext, interact: Option<ModuleInteractInfo>, meter: &mut LoudnessMeter)
+    -> Option<Surface<'static>> {
+
+    let mut canvas =
+        Surface::new(DEFAULT_WIN_SIZE, 90, PixelFormatEnum::RGBA32)
+        .unwrap().into_canvas().unwrap();
+
+    let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+    let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+    let format = |lufs: Option<f32>| lufs.map_or("-".to_string(), |lufs| format!("{lufs:.1} LUFS"));
+    for (name, lufs) in [
+        ("M", meter.momentary()),
+        ("S", meter.short_term()),
+        ("I", meter.integrated()),
+    ] {
+        ui.add_label(&mut canvas, &mut layout, &format!("{name} {}", format(lufs)), None);
+        layout.next_row();
+    }
+
+    if ui.add_button(&mut canvas, &mut layout, &interact, "reset", None) {
+        meter.reset();
+    }
+
+    Some(canvas.into_surface())
+}
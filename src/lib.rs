@@ -1,6 +1,23 @@
 mod utils;
 pub use utils::*;
 
+mod dsp;
+pub use dsp::*;
+
+mod wav;
+pub use wav::*;
+mod audio_file;
+pub use audio_file::*;
+
+mod soundfont;
+pub use soundfont::*;
+
+mod meter;
+pub use meter::*;
+
+mod loader;
+pub use loader::*;
+
 mod modules;
 pub use modules::*;
 
@@ -8,7 +25,7 @@ pub mod ui_utils;
 pub use ui_utils::UiContext;
 
 use serde::{Serialize, Deserialize};
-use std::sync::atomic::*;
+use std::sync::{OnceLock, atomic::*};
 
 pub static SAMPLE_RATE: AtomicU32 = AtomicU32::new(0);
 
@@ -21,23 +38,60 @@ pub fn get_sample_rate() -> u32 {
 }
 
 pub const BPM: f32 = 180.0;
+pub const BEATS_PER_BAR: u64 = 4;
+/// Velocity of notes that don't carry one.
+pub const DEFAULT_VELOCITY: u8 = 100;
+
+/// Position of the transport in samples, advanced once per engine tick.
+pub static TRANSPORT_POSITION: AtomicU64 = AtomicU64::new(0);
+
+pub fn get_transport_position() -> u64 {
+    TRANSPORT_POSITION.load(Ordering::Relaxed)
+}
+
+pub fn advance_transport() {
+    TRANSPORT_POSITION.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn samples_per_beat() -> f64 {
+    get_sample_rate() as f64 * 60.0 / BPM as f64
+}
+
+pub fn samples_per_bar() -> u64 {
+    (samples_per_beat() * BEATS_PER_BAR as f64).round() as u64
+}
+
+static PROJECT_NAME: OnceLock<String> = OnceLock::new();
+
+pub fn set_project_name(name: &str) {
+    let _ = PROJECT_NAME.set(name.to_string());
+}
+
+pub fn get_project_name() -> &'static str {
+    PROJECT_NAME.get().map_or("untitled", String::as_str)
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DataType {
     Audio,
     Notes,
+    /// Slowly changing values like envelopes, meant to modulate parameters.
+    Control,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Data {
     Audio(f32),
     Notes(Box<[Note]>),
+    Control(f32),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Note {
     Midi(u8),
     Freq(f32),
+    /// A MIDI note with a velocity from 1 to 127.
+    MidiVelocity(u8, u8),
 }
 
 pub struct ModuleInteractInfo<'a> {
@@ -52,16 +106,30 @@ pub trait Module {
     fn id(&self) -> &'static str;
     fn get_output_type(&self) -> DataType;
     fn get_inputs(&self) -> Vec<(DataType, &'static str)>;
+    /// Outputs after the one `tick` returns, for modules producing more
+    /// than one kind of data.
+    fn get_extra_outputs(&self) -> Vec<DataType> { Vec::new() }
     fn tick(&mut self) -> Option<Data>;
+    /// Data of extra output `index`, as produced by the last `tick`.
+    fn extra_output(&self, _index: usize) -> Option<Data> { None }
     fn send(&mut self, _input: usize, _data: Data) {}
     fn as_any(&mut self) -> &mut dyn std::any::Any;
     fn draw(&mut self, _ui: &UiContext<'_>, _interact: Option<ModuleInteractInfo>)
         -> Option<sdl2::surface::Surface<'_>> { None }
-    fn execute(&self, _cmd: String) {
+    fn execute(&mut self, _cmd: String) {
         println!("Module::execute is not implemented for: {}", self.title());
     }
     fn get_data(&self) -> Vec<u8> { Vec::new() }
     fn load_data(&mut self, _data: Vec<u8>) {}
+    /// Called by `impl_serialization!` after loading saved data, to check it
+    /// and rebuild the state that is not saved.
+    fn loaded(&mut self) {}
+    /// Called outside the audio callback once the engine sample rate is
+    /// known, so buffers depending on it can be prepared.
+    fn sample_rate_changed(&mut self, _sample_rate: u32) {}
+    /// Called once when the program quits, to finish work on other threads
+    /// such as writing files.
+    fn shutdown(&mut self) {}
 }
 
 #[macro_export]
@@ -82,9 +150,11 @@ macro_rules! define_module {
 }
 
 impl Data {
+    /// Control is accepted as well, so control signals like the envelope
+    /// follower output can be patched into audio inputs.
     fn audio(self) -> f32 {
         match self {
-            Self::Audio(value) => value,
+            Self::Audio(value) | Self::Control(value) => value,
             _ => 0.0
         }
     }
@@ -95,21 +165,45 @@ impl Data {
             _ => Box::new([])
         }
     }
+
+    /// Audio is accepted as well, so oscillators can be used as LFOs.
+    fn control(self) -> f32 {
+        match self {
+            Self::Control(value) | Self::Audio(value) => value,
+            _ => 0.0
+        }
+    }
 }
 
 impl Note {
     pub fn freq(self) -> f32 {
         match self {
-            Self::Midi(note) => midi_to_freq(note),
+            Self::Midi(note) | Self::MidiVelocity(note, _) => midi_to_freq(note),
             Self::Freq(freq) => freq,
         }
     }
 
+    /// The MIDI note number, if the note was given as one.
+    pub fn midi(self) -> Option<u8> {
+        match self {
+            Self::Midi(note) | Self::MidiVelocity(note, _) => Some(note),
+            Self::Freq(_) => None,
+        }
+    }
+
+    pub fn velocity(self) -> u8 {
+        match self {
+            Self::MidiVelocity(_, velocity) => velocity,
+            _ => DEFAULT_VELOCITY,
+        }
+    }
+
     pub fn transpose(self, amount: i16) -> Self {
         match self {
             // TODO fix this math
             Self::Midi(note) => Self::Midi((note as i16 + amount) as u8),
             Self::Freq(freq) => Self::Freq(freq + amount.signum() as f32 * midi_to_freq(amount.abs() as u8)),
+            Self::MidiVelocity(note, velocity) => Self::MidiVelocity((note as i16 + amount) as u8, velocity),
         }
     }
 }
@@ -123,7 +217,10 @@ macro_rules! impl_serialization {
 
         fn load_data(&mut self, data: Vec<u8>) {
             match deserialize(data) {
-                Ok(value) => { *self = value }
+                Ok(value) => {
+                    *self = value;
+                    self.loaded();
+                }
                 Err(err) => eprintln!("deserializing '{}' failed: {}", self.id(), err)
             }
         }
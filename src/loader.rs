@@ -0,0 +1,46 @@
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+/// Work that is too slow for the App lock, like decoding a file, run on its
+/// own thread. Modules start it from `execute` and pick up the result with
+/// `poll` from `draw`.
+pub struct Loader<T> {
+    receiver: Option<Receiver<T>>,
+}
+
+impl<T> Default for Loader<T> {
+    fn default() -> Self {
+        Self { receiver: None }
+    }
+}
+
+impl<T: Send + 'static> Loader<T> {
+    /// Run `work` on a new thread, discarding the result of earlier work
+    /// that has not been picked up yet.
+    pub fn start(&mut self, work: impl FnOnce() -> T + Send + 'static) {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(work());
+        });
+        self.receiver = Some(receiver);
+    }
+
+    /// The result of the work, once it is done.
+    pub fn poll(&mut self) -> Option<T> {
+        let result = self.receiver.as_ref()?.try_recv();
+        match result {
+            Ok(value) => {
+                self.receiver = None;
+                Some(value)
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.receiver = None;
+                None
+            }
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.receiver.is_some()
+    }
+}
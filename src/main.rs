@@ -27,6 +27,7 @@ pub type ModuleId = u16;
 
 fn main() -> anyhow::Result<()> {
     const FILENAME: &'static str = "saved.musikjj";
+    set_project_name(FILENAME.trim_end_matches(".musikjj"));
     let (app, mut gui) = load_file(FILENAME);
 
     let stream = stream_setup_for(Arc::clone(&app))?;
@@ -38,6 +39,9 @@ fn main() -> anyhow::Result<()> {
 
     gui.run(Arc::clone(&app));
 
+    drop(stream);
+    app.lock().unwrap().shutdown();
+
     {
         let app = app.lock().unwrap();
         let modules = app.get_serializeable_modules();
@@ -80,10 +84,17 @@ pub type SerializeableModules = HashMap<ModuleId, (String, Vec<u8>)>;
 struct App {
     #[serde(skip)]
     modules: HashMap<ModuleId, Box<dyn Module + Send>>,
-    conns: HashMap<(ModuleId, usize), ModuleId>,
+    /// Inputs and the outputs connected to them, output 0 being the one
+    /// `Module::tick` returns.
+    conns: HashMap<(ModuleId, usize), (ModuleId, usize)>,
     cached: HashMap<ModuleId, Option<Data>>,
     next_id: ModuleId,
     selection: Option<ModuleId>,
+    /// Output levels of the modules producing audio, shown by the gui.
+    #[serde(skip)]
+    levels: HashMap<ModuleId, LevelMeter>,
+    #[serde(skip)]
+    loudness: LoudnessMeter,
 }
 
 impl App {
@@ -94,6 +105,8 @@ impl App {
             cached: HashMap::new(),
             next_id: 1,
             selection: None,
+            levels: HashMap::from([(0, LevelMeter::default())]),
+            loudness: LoudnessMeter::default(),
         }
     }
 
@@ -117,13 +130,23 @@ impl App {
         self.modules.get_mut(&module).unwrap()
     }
 
-    fn insert_module(&mut self, module: Box<dyn Module + Send>) -> ModuleId {
+    /// Meters are created outside the audio callback, which only updates them.
+    fn add_level_meter(&mut self, id: ModuleId) {
+        self.levels.entry(id).or_default();
+    }
+
+    fn insert_module(&mut self, mut module: Box<dyn Module + Send>) -> ModuleId {
+        let sample_rate = get_sample_rate();
+        if sample_rate != 0 {
+            module.sample_rate_changed(sample_rate);
+        }
         self.modules.insert(self.next_id, module);
+        self.add_level_meter(self.next_id);
         self.next_id += 1;
         self.next_id - 1
     }
 
-    fn connect(&mut self, output: ModuleId, input: (ModuleId, usize)) {
+    fn connect(&mut self, output: (ModuleId, usize), input: (ModuleId, usize)) {
         if let Some(existing_out) = self.conns.get(&input) {
             if output == *existing_out {
                 self.conns.remove(&input);
@@ -152,6 +175,18 @@ impl App {
         self.selection = selection;
     }
 
+    fn sample_rate_changed(&mut self, sample_rate: u32) {
+        for module in self.modules.values_mut() {
+            module.sample_rate_changed(sample_rate);
+        }
+    }
+
+    fn shutdown(&mut self) {
+        for module in self.modules.values_mut() {
+            module.shutdown();
+        }
+    }
+
     fn execute(&mut self, cmd: String) {
         if let Some(selection) = self.selection {
             self.module(selection).execute(cmd);
@@ -168,8 +203,8 @@ impl App {
         let data = if id != 0 {
             let inputs = self.modules[&id].get_inputs();
             for input_index in 0..inputs.len() {
-                if let Some(input_id) = self.conns.get(&(id, input_index)) {
-                    let data = self.get_output(*input_id);
+                if let Some(output) = self.conns.get(&(id, input_index)).copied() {
+                    let data = self.read_output(output);
                     if let Some(data) = data {
                         self.module(id).send(input_index, data);
                     }
@@ -178,8 +213,8 @@ impl App {
             self.module(id).tick()
 
         } else {
-            if let Some(input_id) = self.conns.get(&(id, 0)) {
-                self.get_output(*input_id)
+            if let Some(output) = self.conns.get(&(id, 0)).copied() {
+                self.read_output(output)
             } else {
                 if id == 0 {
                     Some(Data::Audio(0.0))
@@ -189,16 +224,33 @@ impl App {
             }
         };
 
+        if let Some(Data::Audio(value)) = data
+                && let Some(level) = self.levels.get_mut(&id) {
+            level.process(value);
+        }
+
         self.cached.insert(id, data.clone());
         data
     }
 
+    /// Data of any output of a module, ticking it first if needed.
+    fn read_output(&mut self, (id, output): (ModuleId, usize)) -> Option<Data> {
+        let data = self.get_output(id);
+        match output {
+            0 => data,
+            _ => self.modules.get(&id)?.extra_output(output - 1),
+        }
+    }
+
     fn tick(&mut self) -> f32 {
         self.cached.clear();
-        match self.get_output(0) {
+        let output = match self.get_output(0) {
             Some(Data::Audio(audio)) => audio,
             _ => 0.0
-        }
+        };
+        self.loudness.process(output);
+        advance_transport();
+        output
     }
 }
 
@@ -210,6 +262,8 @@ impl Clone for App {
             cached: self.cached.clone(),
             next_id: self.next_id.clone(),
             selection: self.selection.clone(),
+            levels: HashMap::new(),
+            loudness: LoudnessMeter::default(),
         }
     }
 }
@@ -271,6 +325,7 @@ fn make_stream<T>(
 
     let num_channels = config.channels as usize;
     set_sample_rate(config.sample_rate);
+    app.lock().unwrap().sample_rate_changed(config.sample_rate);
 
     let err_fn = |err| eprintln!(
         "error building output sound stream: {err}");
@@ -0,0 +1,199 @@
+use crate::*;
+
+/// Peak falloff of the level meter in dB per second.
+const PEAK_FALLOFF: f32 = 20.0;
+/// Integration time of the RMS level in seconds.
+const RMS_TIME: f32 = 0.3;
+
+/// Length of the loudness sub-blocks in seconds. Momentary loudness spans
+/// 4 of them and short-term loudness 30, integrated loudness is measured on
+/// momentary blocks overlapping by 75% as in ITU-R BS.1770.
+const SUB_BLOCK_TIME: f32 = 0.1;
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+const ABSOLUTE_GATE: f32 = -70.0;
+const RELATIVE_GATE: f32 = -10.0;
+const MAX_LOUDNESS: f32 = 10.0;
+/// Resolution of the block loudness histogram in LU.
+const HISTOGRAM_STEP: f32 = 0.1;
+const HISTOGRAM_SIZE: usize = ((MAX_LOUDNESS - ABSOLUTE_GATE) / HISTOGRAM_STEP) as usize;
+
+pub fn amplitude_to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.max(1e-9).log10()
+}
+
+fn mean_square_to_lufs(mean_square: f32) -> f32 {
+    -0.691 + 10.0 * mean_square.max(1e-12).log10()
+}
+
+/// The two K-weighting filters of BS.1770, a high shelf modelling the
+/// head followed by a highpass, designed for any sample rate.
+fn k_weighting(sample_rate: f32) -> [Biquad; 2] {
+    use std::f32::consts::PI;
+
+    let k = (PI * 1681.9745 / sample_rate).tan();
+    let q = 0.70717525;
+    let vh = 10.0_f32.powf(3.9998438 / 20.0);
+    let vb = vh.powf(0.49966677);
+    let shelf = Biquad::from_coefficients(
+        vh + vb * k / q + k * k,
+        2.0 * (k * k - vh),
+        vh - vb * k / q + k * k,
+        1.0 + k / q + k * k,
+        2.0 * (k * k - 1.0),
+        1.0 - k / q + k * k,
+    );
+
+    let k = (PI * 38.13547 / sample_rate).tan();
+    let q = 0.50032704;
+    let highpass = Biquad::from_coefficients(
+        1.0,
+        -2.0,
+        1.0,
+        1.0 + k / q + k * k,
+        2.0 * (k * k - 1.0),
+        1.0 - k / q + k * k,
+    );
+
+    [shelf, highpass]
+}
+
+/// Peak and RMS level of a signal with meter ballistics.
+#[derive(Clone, Copy, Default)]
+pub struct LevelMeter {
+    peak: f32,
+    mean_square: f32,
+    sample_rate: u32,
+    peak_decay: f32,
+    rms_coefficient: f32,
+}
+
+impl LevelMeter {
+    pub fn process(&mut self, value: f32) {
+        let sample_rate = get_sample_rate();
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            let sample_rate = sample_rate.max(1) as f32;
+            self.peak_decay = 10.0_f32.powf(-PEAK_FALLOFF / 20.0 / sample_rate);
+            self.rms_coefficient = 1.0 - (-1.0 / (RMS_TIME * sample_rate)).exp();
+        }
+
+        self.peak = (self.peak * self.peak_decay).max(value.abs());
+        self.mean_square += (value * value - self.mean_square) * self.rms_coefficient;
+    }
+
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+
+    pub fn rms(&self) -> f32 {
+        self.mean_square.sqrt()
+    }
+}
+
+/// Loudness meter following ITU-R BS.1770 for a single channel: momentary,
+/// short-term and gated integrated loudness in LUFS.
+///
+/// Integrated loudness keeps a histogram of block loudness instead of every
+/// block, so it never allocates and can run in the audio callback.
+#[derive(Clone)]
+pub struct LoudnessMeter {
+    sample_rate: u32,
+    filters: [Biquad; 2],
+    sub_blocks: [f32; SHORT_TERM_BLOCKS],
+    sub_block_index: usize,
+    sub_block_count: usize,
+    sum: f32,
+    samples: usize,
+    histogram: [(u32, f64); HISTOGRAM_SIZE],
+}
+
+impl Default for LoudnessMeter {
+    fn default() -> Self {
+        Self {
+            sample_rate: 0,
+            filters: Default::default(),
+            sub_blocks: [0.0; SHORT_TERM_BLOCKS],
+            sub_block_index: 0,
+            sub_block_count: 0,
+            sum: 0.0,
+            samples: 0,
+            histogram: [(0, 0.0); HISTOGRAM_SIZE],
+        }
+    }
+}
+
+impl LoudnessMeter {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn process(&mut self, value: f32) {
+        let sample_rate = get_sample_rate();
+        if self.sample_rate != sample_rate {
+            self.reset();
+            self.sample_rate = sample_rate;
+            self.filters = k_weighting(sample_rate as f32);
+        }
+        if sample_rate == 0 {
+            return
+        }
+
+        let mut value = value;
+        for filter in self.filters.iter_mut() {
+            value = filter.process(value);
+        }
+        self.sum += value * value;
+        self.samples += 1;
+
+        if (sample_rate as f32 * SUB_BLOCK_TIME) as usize <= self.samples {
+            self.sub_blocks[self.sub_block_index] = self.sum / self.samples as f32;
+            self.sub_block_index = (self.sub_block_index + 1) % SHORT_TERM_BLOCKS;
+            self.sub_block_count += 1;
+            self.sum = 0.0;
+            self.samples = 0;
+
+            if MOMENTARY_BLOCKS <= self.sub_block_count {
+                let mean_square = self.mean_square(MOMENTARY_BLOCKS);
+                let lufs = mean_square_to_lufs(mean_square);
+                if ABSOLUTE_GATE < lufs {
+                    let bin = (((lufs - ABSOLUTE_GATE) / HISTOGRAM_STEP) as usize).min(HISTOGRAM_SIZE - 1);
+                    self.histogram[bin].0 += 1;
+                    self.histogram[bin].1 += mean_square as f64;
+                }
+            }
+        }
+    }
+
+    /// Mean square of the last `blocks` sub-blocks.
+    fn mean_square(&self, blocks: usize) -> f32 {
+        (1..=blocks)
+            .map(|i| self.sub_blocks[(self.sub_block_index + SHORT_TERM_BLOCKS - i) % SHORT_TERM_BLOCKS])
+            .sum::<f32>() / blocks as f32
+    }
+
+    fn loudness(&self, blocks: usize) -> Option<f32> {
+        (blocks <= self.sub_block_count)
+            .then(|| mean_square_to_lufs(self.mean_square(blocks)))
+    }
+
+    pub fn momentary(&self) -> Option<f32> {
+        self.loudness(MOMENTARY_BLOCKS)
+    }
+
+    pub fn short_term(&self) -> Option<f32> {
+        self.loudness(SHORT_TERM_BLOCKS)
+    }
+
+    pub fn integrated(&self) -> Option<f32> {
+        let gated_mean = |gate: f32| {
+            let start = (((gate - ABSOLUTE_GATE) / HISTOGRAM_STEP).max(0.0) as usize).min(HISTOGRAM_SIZE);
+            let (count, sum) = self.histogram[start..].iter()
+                .fold((0, 0.0), |(count, sum), bin| (count + bin.0, sum + bin.1));
+            (count != 0).then(|| (sum / count as f64) as f32)
+        };
+
+        let relative_gate = mean_square_to_lufs(gated_mean(ABSOLUTE_GATE)?) + RELATIVE_GATE;
+        gated_mean(relative_gate.max(ABSOLUTE_GATE)).map(mean_square_to_lufs)
+    }
+}
@@ -16,6 +16,84 @@ pub use mixer::Mixer;
 mod transpose;
 pub use transpose::Transpose;
 
+mod distortion;
+pub use distortion::{Distortion, DistortionMode};
+
+mod modulation;
+pub use modulation::{Modulation, ModulationMode};
+
+mod equalizer;
+pub use equalizer::{Equalizer, BandType};
+
+mod sampler;
+pub use sampler::Sampler;
+
+mod soundfont_player;
+pub use soundfont_player::SoundfontPlayer;
+
+mod drum_machine;
+pub use drum_machine::{DrumMachine, DrumOutput};
+
+mod drum_synth;
+pub use drum_synth::{DrumSynth, DrumKind};
+
+mod recorder;
+pub use recorder::Recorder;
+
+mod file_player;
+pub use file_player::FilePlayer;
+
+mod looper;
+pub use looper::Looper;
+
+mod oscilloscope;
+pub use oscilloscope::{Oscilloscope, TriggerEdge};
+
+mod spectrum;
+pub use spectrum::{Spectrum, SpectrumView};
+
+mod tuner;
+pub use tuner::Tuner;
+
+mod pitch_tracker;
+pub use pitch_tracker::PitchTracker;
+
+mod envelope_follower;
+pub use envelope_follower::EnvelopeFollower;
+
+mod noise;
+pub use noise::{Noise, NoiseColor};
+
+mod fm_operator;
+pub use fm_operator::FmOperator;
+
+mod pluck;
+pub use pluck::{Pluck, Excitation};
+
+mod additive;
+pub use additive::Additive;
+
+mod granular;
+pub use granular::{Granular, GrainSource};
+
+mod vocoder;
+pub use vocoder::Vocoder;
+
+mod pitch_shifter;
+pub use pitch_shifter::PitchShifter;
+
+mod formant_filter;
+pub use formant_filter::FormantFilter;
+
+mod spectral;
+pub use spectral::Spectral;
+
+mod math;
+pub use math::{
+    Signal, Constant, Add, Multiply, Invert, ScaleOffset, MinMax, Crossfade,
+    SampleHold, SlewLimiter, Comparator,
+};
+
 use crate::*;
 
 macro_rules! define_module_from_id {
@@ -38,4 +116,38 @@ define_module_from_id! {
     Sequencer,
     Mixer,
     Transpose,
+    Distortion,
+    Modulation,
+    Equalizer,
+    Sampler,
+    SoundfontPlayer,
+    DrumMachine,
+    DrumSynth,
+    Recorder,
+    FilePlayer,
+    Looper,
+    Oscilloscope,
+    Spectrum,
+    Tuner,
+    PitchTracker,
+    EnvelopeFollower,
+    Noise,
+    FmOperator,
+    Pluck,
+    Additive,
+    Granular,
+    Vocoder,
+    PitchShifter,
+    FormantFilter,
+    Spectral,
+    Constant,
+    Add,
+    Multiply,
+    Invert,
+    ScaleOffset,
+    MinMax,
+    Crossfade,
+    SampleHold,
+    SlewLimiter,
+    Comparator,
 }
@@ -0,0 +1,274 @@
+use crate::*;
+
+const HARMONICS: usize = 64;
+const TABLE_SIZE: usize = 2048;
+const MAX_VOICES: usize = 8;
+
+const BAR_WIDTH: u32 = 5;
+const GRAPH_WIDTH: u32 = BAR_WIDTH * HARMONICS as u32;
+const GRAPH_HEIGHT: u32 = 120;
+
+/// Amplitude of harmonic `k` in one of the preset waveforms.
+fn preset(name: &str, k: usize) -> f32 {
+    match name {
+        "sine" if k == 1 => 1.0,
+        "saw" => 1.0 / k as f32,
+        "square" if !k.is_multiple_of(2) => 1.0 / k as f32,
+        _ => 0.0,
+    }
+}
+
+#[derive(Clone, Default)]
+struct Voice {
+    freq: f32,
+    phase: f32,
+    /// One period with the harmonics above nyquist left out.
+    table: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Additive {
+    harmonics: Vec<f32>,
+    /// Spectral tilt, negative values weaken higher harmonics.
+    brightness: f32,
+    /// Balance between odd (negative) and even (positive) harmonics.
+    odd_even: f32,
+
+    #[serde(skip)]
+    voices: Vec<Voice>,
+    // scratch space for building the tables
+    #[serde(skip)]
+    re: Vec<f32>,
+    #[serde(skip)]
+    im: Vec<f32>,
+}
+
+impl Additive {
+    pub fn new() -> Self {
+        let mut additive = Self {
+            // start out as a saw wave
+            harmonics: (1..=HARMONICS).map(|k| preset("saw", k)).collect(),
+            brightness: 0.0,
+            odd_even: 0.0,
+            voices: Vec::new(),
+            re: Vec::new(),
+            im: Vec::new(),
+        };
+        additive.allocate();
+        additive
+    }
+
+    /// Allocate the voices and scratch buffers up front, so new notes
+    /// don't allocate in the audio callback.
+    fn allocate(&mut self) {
+        self.voices = vec![Voice { table: Vec::with_capacity(TABLE_SIZE), ..Default::default() }; MAX_VOICES];
+        self.re = vec![0.0; TABLE_SIZE];
+        self.im = vec![0.0; TABLE_SIZE];
+    }
+
+    /// Amplitude of harmonic `k` (starting at 1) after brightness and
+    /// odd/even balance.
+    fn weight(&self, k: usize) -> f32 {
+        let balance = if k == 1 {
+            1.0
+        } else if k.is_multiple_of(2) {
+            (1.0 + self.odd_even).min(1.0)
+        } else {
+            (1.0 - self.odd_even).min(1.0)
+        };
+        self.harmonics[k - 1] * (k as f32).powf(self.brightness * 2.0) * balance
+    }
+
+    /// Build the table of a voice from the harmonics below nyquist, using
+    /// an inverse FFT, and normalize it to full scale.
+    fn build_table(&mut self, voice: usize) {
+        let nyquist = get_sample_rate() as f32 / 2.0;
+        let freq = self.voices[voice].freq;
+        self.re.fill(0.0);
+        self.im.fill(0.0);
+
+        for k in 1..=HARMONICS {
+            if nyquist <= freq * k as f32 {
+                break
+            }
+            // the FFT of the conjugated spectrum of a sine series
+            let weight = self.weight(k);
+            self.im[k] = weight / 2.0;
+            self.im[TABLE_SIZE - k] = -weight / 2.0;
+        }
+        fft(&mut self.re, &mut self.im);
+
+        let peak = self.re.iter().fold(0.0_f32, |peak, value| peak.max(value.abs()));
+        let gain = if peak == 0.0 { 0.0 } else { 1.0 / peak };
+        let table = &mut self.voices[voice].table;
+        table.clear();
+        table.extend(self.re.iter().map(|value| value * gain));
+    }
+
+    fn update_tables(&mut self) {
+        for voice in 0..self.voices.len() {
+            if self.voices[voice].freq != 0.0 {
+                self.build_table(voice);
+            }
+        }
+    }
+
+    fn set_notes(&mut self, notes: &[Note]) {
+        for voice in 0..self.voices.len() {
+            let freq = notes.get(voice).map_or(0.0, |note| note.freq());
+            if self.voices[voice].freq != freq {
+                self.voices[voice].freq = freq;
+                self.voices[voice].phase = 0.0;
+                if freq != 0.0 {
+                    self.build_table(voice);
+                }
+            }
+        }
+    }
+
+    /// Set harmonic amplitudes by dragging over the bar graph.
+    fn interact(&mut self, info: &ModuleInteractInfo) -> bool {
+        let (x, y) = (info.x as u32, info.y as u32);
+        if GRAPH_WIDTH <= x || GRAPH_HEIGHT <= y {
+            return false
+        }
+
+        let mouse = info.event_pump.mouse_state();
+        let amplitude = if mouse.left() {
+            1.0 - y as f32 / GRAPH_HEIGHT as f32
+        } else if mouse.right() {
+            0.0
+        } else {
+            return false
+        };
+
+        self.harmonics[(x / BAR_WIDTH) as usize] = amplitude;
+        true
+    }
+
+    fn draw_graph(&self, canvas: &mut sdl2::render::SurfaceCanvas) {
+        use sdl2::{
+            pixels::Color,
+            rect::Rect,
+        };
+
+        canvas.set_draw_color(Color::RGB(230, 230, 230));
+        canvas.fill_rect(Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT)).unwrap();
+
+        for (i, amplitude) in self.harmonics.iter().enumerate() {
+            let height = (amplitude.clamp(0.0, 1.0) * GRAPH_HEIGHT as f32) as u32;
+            if height == 0 {
+                continue
+            }
+            canvas.set_draw_color(if i % 2 == 0 {
+                Color::RGB(0, 0, 200)
+            } else {
+                Color::RGB(80, 80, 230)
+            });
+            let x = i as i32 * BAR_WIDTH as i32;
+            canvas.fill_rect(Rect::new(x, (GRAPH_HEIGHT - height) as i32, BAR_WIDTH - 1, height)).unwrap();
+        }
+
+        // the effective amplitudes after brightness and odd/even balance
+        let weights: Vec<f32> = (1..=HARMONICS).map(|k| self.weight(k)).collect();
+        let max = weights.iter().fold(0.0_f32, |max, weight| max.max(*weight));
+        if 0.0 < max {
+            canvas.set_draw_color(Color::RGB(200, 0, 0));
+            for (i, weight) in weights.iter().enumerate() {
+                let y = GRAPH_HEIGHT as i32 - (weight / max * GRAPH_HEIGHT as f32) as i32;
+                let x = i as i32 * BAR_WIDTH as i32;
+                canvas.draw_line((x, y), (x + BAR_WIDTH as i32 - 2, y)).unwrap();
+            }
+        }
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_rect(Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT)).unwrap();
+    }
+}
+
+impl Module for Additive {
+    fn tick(&mut self) -> Option<Data> {
+        let sample_rate = get_sample_rate() as f32;
+        let mut value = 0.0;
+        let mut active = 0;
+
+        for voice in self.voices.iter_mut().filter(|voice| voice.freq != 0.0) {
+            let position = voice.phase * TABLE_SIZE as f32;
+            let index = position as usize;
+            let frac = position - index as f32;
+            let a = voice.table[index % TABLE_SIZE];
+            let b = voice.table[(index + 1) % TABLE_SIZE];
+            value += a + (b - a) * frac;
+            active += 1;
+
+            voice.phase = (voice.phase + voice.freq / sample_rate) % 1.0;
+        }
+
+        Some(Data::Audio(if active != 0 {
+            value / active as f32
+        } else {
+            0.0
+        }))
+    }
+
+    define_module! {
+        title: "Additive",
+        id: "additive",
+        output: Audio,
+        inputs: [(Notes, "notes")],
+    }
+
+    impl_serialization!();
+
+    fn loaded(&mut self) {
+        self.harmonics.resize(HARMONICS, 0.0);
+        self.allocate();
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        if get_sample_rate() == 0 {
+            return
+        }
+        self.set_notes(&data.notes());
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (GRAPH_WIDTH, GRAPH_HEIGHT + 90);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        let mut changed = interact.as_ref().is_some_and(|info| self.interact(info));
+
+        self.draw_graph(&mut canvas);
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new(
+            (0, GRAPH_HEIGHT as i32 + 5), mouse_pos);
+
+        for name in ["sine", "saw", "square", "clear"] {
+            if ui.add_button(&mut canvas, &mut layout, &interact, name, None) {
+                self.harmonics = (1..=HARMONICS).map(|k| preset(name, k)).collect();
+                changed = true;
+            }
+        }
+        layout.next_row();
+
+        changed |= ui.add_param(&mut canvas, &mut layout, &interact, "brightness", &mut self.brightness, 0.1, -1.0, 1.0);
+        changed |= ui.add_param(&mut canvas, &mut layout, &interact, "odd/even", &mut self.odd_even, 0.1, -1.0, 1.0);
+
+        if changed {
+            self.update_tables();
+        }
+
+        Some(canvas.into_surface())
+    }
+}
@@ -0,0 +1,220 @@
+use crate::*;
+
+const MAX_OVERSAMPLING: usize = 8;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum DistortionMode {
+    SoftClip,
+    HardClip,
+    Foldback,
+    Bitcrush,
+    Downsample,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Distortion {
+    mode: DistortionMode,
+    drive: f32,
+    mix: f32,
+    bits: f32,
+    downsample: f32,
+    oversampling: usize,
+
+    #[serde(skip)]
+    input: f32,
+    #[serde(skip)]
+    last_input: f32,
+    #[serde(skip)]
+    filters: [Biquad; 2],
+    #[serde(skip)]
+    filter_rate: u32,
+    #[serde(skip)]
+    held: f32,
+    #[serde(skip)]
+    hold_counter: f32,
+}
+
+impl Distortion {
+    pub fn new() -> Self {
+        Self {
+            mode: DistortionMode::SoftClip,
+            drive: 2.0,
+            mix: 1.0,
+            bits: 8.0,
+            downsample: 4.0,
+            oversampling: 4,
+            input: 0.0,
+            last_input: 0.0,
+            filters: Default::default(),
+            filter_rate: 0,
+            held: 0.0,
+            hold_counter: 0.0,
+        }
+    }
+
+    fn shape(&self, value: f32) -> f32 {
+        let value = value * self.drive;
+        match self.mode {
+            DistortionMode::SoftClip => value.tanh(),
+            DistortionMode::HardClip => value.clamp(-1.0, 1.0),
+            DistortionMode::Foldback => {
+                // reflect the signal back into -1..1 until it fits
+                let folded = (value + 1.0).rem_euclid(4.0);
+                if folded < 2.0 { folded - 1.0 } else { 3.0 - folded }
+            }
+            DistortionMode::Bitcrush => {
+                let steps = 2.0_f32.powf(self.bits - 1.0);
+                (value.clamp(-1.0, 1.0) * steps).round() / steps
+            }
+            DistortionMode::Downsample => value.clamp(-1.0, 1.0),
+        }
+    }
+
+    fn update_filters(&mut self) {
+        let sample_rate = get_sample_rate();
+        if sample_rate == self.filter_rate {
+            return
+        }
+
+        // anti-aliasing filter running at the oversampled rate,
+        // cutting just below the original nyquist frequency
+        let rate = sample_rate as f32 * self.oversampling as f32;
+        let cutoff = sample_rate as f32 * 0.45;
+        self.filters = [
+            Biquad::lowpass(cutoff, 0.54, rate),
+            Biquad::lowpass(cutoff, 1.31, rate),
+        ];
+        self.filter_rate = sample_rate;
+    }
+
+    fn oversampled(&mut self) -> f32 {
+        let factor = self.oversampling;
+        let mut output = 0.0;
+
+        for i in 1..=factor {
+            let t = i as f32 / factor as f32;
+            let value = self.last_input + (self.input - self.last_input) * t;
+            output = self.shape(value);
+            for filter in self.filters.iter_mut() {
+                output = filter.process(output);
+            }
+        }
+
+        output
+    }
+
+    fn downsampled(&mut self) -> f32 {
+        self.hold_counter -= 1.0;
+        if self.hold_counter <= 0.0 {
+            self.held = self.shape(self.input);
+            self.hold_counter += self.downsample;
+        }
+        self.held
+    }
+
+    fn cycle_mode(&mut self) {
+        use DistortionMode::*;
+        self.mode = match self.mode {
+            SoftClip => HardClip,
+            HardClip => Foldback,
+            Foldback => Bitcrush,
+            Bitcrush => Downsample,
+            Downsample => SoftClip,
+        };
+    }
+
+    fn cycle_oversampling(&mut self) {
+        self.oversampling = if self.oversampling < MAX_OVERSAMPLING {
+            self.oversampling * 2
+        } else {
+            1
+        };
+        // force the filters to be recalculated for the new rate
+        self.filter_rate = 0;
+        self.filters = Default::default();
+    }
+}
+
+impl Module for Distortion {
+    fn tick(&mut self) -> Option<Data> {
+        self.update_filters();
+
+        let wet = match self.mode {
+            // sample rate reduction is supposed to alias
+            DistortionMode::Downsample => self.downsampled(),
+            _ => self.oversampled(),
+        };
+        self.last_input = self.input;
+
+        Some(Data::Audio(self.input * (1.0 - self.mix) + wet * self.mix))
+    }
+
+    define_module! {
+        title: "Distortion",
+        id: "distortion",
+        output: Audio,
+        inputs: [(Audio, "audio")],
+    }
+
+    impl_serialization!();
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.audio();
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (260, 150);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        ui.add_label(&mut canvas, &mut layout, self.mode.as_str(), Some(10));
+        if ui.add_button(&mut canvas, &mut layout, &interact, "cycle mode", None) {
+            self.cycle_mode();
+        }
+        layout.next_row();
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "drive", &mut self.drive, 0.5, 0.5, 20.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "mix", &mut self.mix, 0.1, 0.0, 1.0);
+
+        match self.mode {
+            DistortionMode::Bitcrush => {
+                ui.add_param(&mut canvas, &mut layout, &interact, "bits", &mut self.bits, 1.0, 1.0, 16.0);
+            }
+            DistortionMode::Downsample => {
+                ui.add_param(&mut canvas, &mut layout, &interact, "reduction", &mut self.downsample, 1.0, 1.0, 64.0);
+            }
+            _ => {}
+        }
+
+        ui.add_label(&mut canvas, &mut layout, &format!("oversampling: {}x", self.oversampling), Some(16));
+        if ui.add_button(&mut canvas, &mut layout, &interact, "cycle", None) {
+            self.cycle_oversampling();
+        }
+
+        Some(canvas.into_surface())
+    }
+}
+
+impl DistortionMode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::SoftClip => "soft clip",
+            Self::HardClip => "hard clip",
+            Self::Foldback => "foldback",
+            Self::Bitcrush => "bitcrush",
+            Self::Downsample => "downsample",
+        }
+    }
+}
@@ -0,0 +1,411 @@
+use crate::*;
+
+const MAX_STEPS: usize = 16;
+const VELOCITIES: [u8; 4] = [127, 100, 70, 40];
+// least common multiple of all track lengths, so the step counter can wrap
+const STEP_PERIOD: usize = 720720;
+
+const NAME_WIDTH: i32 = 110;
+const CELL_SIZE: u32 = 20;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DrumOutput {
+    /// Play the sample slot of the track on the audio output.
+    Audio,
+    /// Send the trigger note of the track with the velocity of the step on
+    /// the notes output.
+    Triggers,
+}
+
+impl DrumOutput {
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Audio => "plays sample",
+            Self::Triggers => "sends note",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Self::Audio => Self::Triggers,
+            Self::Triggers => Self::Audio,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Track {
+    name: String,
+    /// Velocity per step, 0 meaning no hit.
+    steps: [u8; MAX_STEPS],
+    length: usize,
+    muted: bool,
+    note: u8,
+    output: DrumOutput,
+    path: Option<String>,
+
+    // the loaded file, and the same resampled to the engine sample rate
+    #[serde(skip)]
+    source: AudioBuffer,
+    #[serde(skip)]
+    sample: AudioBuffer,
+    #[serde(skip)]
+    position: Option<usize>,
+    #[serde(skip)]
+    velocity: f32,
+    #[serde(skip)]
+    loader: Loader<(String, anyhow::Result<(AudioBuffer, AudioBuffer)>)>,
+}
+
+impl Track {
+    fn new(name: &str, note: u8) -> Self {
+        Self {
+            name: name.to_string(),
+            steps: [0; MAX_STEPS],
+            length: MAX_STEPS,
+            muted: false,
+            note,
+            output: DrumOutput::Audio,
+            path: None,
+            source: AudioBuffer::default(),
+            sample: AudioBuffer::default(),
+            position: None,
+            velocity: 0.0,
+            loader: Loader::default(),
+        }
+    }
+
+    fn load(&mut self, path: &str) -> anyhow::Result<()> {
+        let sample_rate = get_sample_rate();
+        self.set_sample(path.to_string(), AudioBuffer::load_wav_resampled(path, sample_rate)?);
+        Ok(())
+    }
+
+    /// Decode `path` on its own thread, the sample is swapped in by `poll`.
+    fn start_load(&mut self, path: &str) {
+        let path = path.to_string();
+        let sample_rate = get_sample_rate();
+        self.loader.start(move || {
+            let result = AudioBuffer::load_wav_resampled(&path, sample_rate);
+            (path, result)
+        });
+    }
+
+    fn poll(&mut self) {
+        if let Some((path, result)) = self.loader.poll() {
+            match result {
+                Ok(buffers) => {
+                    self.set_sample(path.clone(), buffers);
+                    println!("loaded {path} into {}", self.name);
+                }
+                Err(err) => println!("loading {path} failed: {err}"),
+            }
+        }
+    }
+
+    fn set_sample(&mut self, path: String, (source, sample): (AudioBuffer, AudioBuffer)) {
+        self.source = source;
+        self.sample = sample;
+        self.position = None;
+        self.path = Some(path);
+        self.resample(get_sample_rate());
+    }
+
+    fn resample(&mut self, sample_rate: u32) {
+        if sample_rate != 0 && self.sample.sample_rate != sample_rate && !self.source.is_empty() {
+            self.sample = self.source.resampled(sample_rate);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DrumMachine {
+    tracks: Vec<Track>,
+    selected: usize,
+
+    #[serde(skip)]
+    step: usize,
+    #[serde(skip)]
+    counter: f64,
+    /// Notes of the trigger tracks hitting on this tick.
+    #[serde(skip)]
+    notes: Option<Data>,
+}
+
+impl DrumMachine {
+    pub fn new() -> Self {
+        Self {
+            tracks: vec![
+                Track::new("kick", 36),
+                Track::new("snare", 38),
+                Track::new("closed hat", 42),
+                Track::new("open hat", 46),
+            ],
+            selected: 0,
+            step: 0,
+            counter: 0.0,
+            notes: None,
+        }
+    }
+
+    /// The step of `track` that was played last.
+    fn track_step(&self, track: &Track) -> usize {
+        (self.step + STEP_PERIOD - 1) % STEP_PERIOD % track.length
+    }
+
+    /// Play the next step, returning the notes of the tracks hitting on it.
+    fn advance(&mut self) -> Vec<Note> {
+        let step = self.step;
+        self.step = (self.step + 1) % STEP_PERIOD;
+
+        let mut notes = Vec::new();
+        for track in self.tracks.iter_mut() {
+            let velocity = track.steps[step % track.length];
+            if velocity == 0 || track.muted {
+                continue
+            }
+            match track.output {
+                DrumOutput::Audio => {
+                    track.position = Some(0);
+                    track.velocity = velocity as f32 / 127.0;
+                }
+                DrumOutput::Triggers => notes.push(Note::MidiVelocity(track.note, velocity)),
+            }
+        }
+        notes
+    }
+
+    fn play_samples(&mut self) -> f32 {
+        let mut value = 0.0;
+        for track in self.tracks.iter_mut() {
+            if let Some(position) = track.position {
+                match track.sample.samples.get(position) {
+                    Some(sample) => {
+                        value += sample * track.velocity;
+                        track.position = Some(position + 1);
+                    }
+                    None => track.position = None,
+                }
+            }
+        }
+        value
+    }
+
+    fn draw_grid(&mut self, canvas: &mut sdl2::render::SurfaceCanvas,
+            interact: &Option<ModuleInteractInfo>) {
+
+        use sdl2::{
+            pixels::Color,
+            mouse::MouseButton,
+            rect::Rect,
+        };
+
+        // toggle steps with left click, cycle velocity with right click
+        if let Some(info) = interact
+                && let Some(button) = info.click {
+            let column = (info.x as i32 - NAME_WIDTH) / CELL_SIZE as i32;
+            let row = info.y as usize / CELL_SIZE as usize;
+
+            if NAME_WIDTH <= info.x as i32 && (column as usize) < MAX_STEPS
+                    && let Some(track) = self.tracks.get_mut(row) {
+                let step = &mut track.steps[column as usize];
+                match button {
+                    MouseButton::Left => {
+                        *step = if *step == 0 { VELOCITIES[1] } else { 0 };
+                    }
+                    MouseButton::Right => {
+                        let index = VELOCITIES.iter().position(|velocity| velocity == step);
+                        *step = VELOCITIES[index.map_or(0, |i| (i + 1) % VELOCITIES.len())];
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for (row, track) in self.tracks.iter().enumerate() {
+            let current = self.track_step(track);
+            for (column, velocity) in track.steps.iter().enumerate() {
+                let rect = Rect::new(
+                    NAME_WIDTH + (column as u32 * CELL_SIZE) as i32,
+                    (row as u32 * CELL_SIZE) as i32,
+                    CELL_SIZE,
+                    CELL_SIZE,
+                );
+
+                let shade = 255 - (*velocity as u32 * 255 / 127) as u8;
+                let color = if track.length <= column {
+                    Color::RGB(150, 150, 150)
+                } else if *velocity != 0 {
+                    if track.muted {
+                        Color::RGB(shade, shade, shade)
+                    } else {
+                        Color::RGB(shade, shade, 200)
+                    }
+                } else if column % 4 == 0 {
+                    Color::RGB(215, 215, 215)
+                } else {
+                    Color::RGB(235, 235, 235)
+                };
+
+                canvas.set_draw_color(color);
+                canvas.fill_rect(rect).unwrap();
+                canvas.set_draw_color(if column == current {
+                    Color::RGB(200, 0, 0)
+                } else {
+                    Color::BLACK
+                });
+                canvas.draw_rect(rect).unwrap();
+            }
+        }
+    }
+}
+
+impl Module for DrumMachine {
+    fn tick(&mut self) -> Option<Data> {
+        let sample_rate = get_sample_rate();
+        if sample_rate == 0 {
+            return None
+        }
+
+        let step_samples = sample_rate as f64 * 60.0 / BPM as f64 / 4.0;
+        self.counter += 1.0;
+
+        self.notes = if step_samples <= self.counter {
+            self.counter -= step_samples;
+            Some(Data::Notes(self.advance().into()))
+        } else {
+            None
+        };
+
+        Some(Data::Audio(self.play_samples()))
+    }
+
+    define_module! {
+        title: "DrumMachine",
+        id: "drum_machine",
+        output: Audio,
+        inputs: [],
+    }
+
+    fn get_extra_outputs(&self) -> Vec<DataType> {
+        vec![DataType::Notes]
+    }
+
+    fn extra_output(&self, index: usize) -> Option<Data> {
+        match index {
+            0 => self.notes.clone(),
+            _ => None,
+        }
+    }
+
+    impl_serialization!();
+
+    fn loaded(&mut self) {
+        for track in self.tracks.iter_mut() {
+            // the step counter wraps by the length of the track
+            track.length = track.length.clamp(1, MAX_STEPS);
+            if let Some(path) = track.path.clone()
+                    && let Err(err) = track.load(&path) {
+                eprintln!("loading sample '{path}' failed: {err}");
+            }
+        }
+    }
+
+    fn sample_rate_changed(&mut self, sample_rate: u32) {
+        for track in self.tracks.iter_mut() {
+            track.resample(sample_rate);
+        }
+    }
+
+    fn execute(&mut self, cmd: String) {
+        let mut args = cmd.splitn(3, ' ');
+        let command = args.next();
+        let track = args.next().and_then(|track| track.parse::<usize>().ok())
+            .and_then(|track| self.tracks.get_mut(track));
+        let arg = args.next().map(str::trim);
+
+        match (command, track, arg) {
+            (Some("load"), Some(track), Some(path)) => track.start_load(path),
+            (Some("name"), Some(track), Some(name)) if !name.is_empty() => {
+                track.name = name.to_string();
+            }
+            (Some("note"), Some(track), Some(note)) => match note.parse() {
+                Ok(note) => track.note = note,
+                Err(err) => println!("invalid note: {err}"),
+            }
+            _ => println!("commands: load <track> <file.wav>, name <track> <name>, note <track> <midi note>"),
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        for track in self.tracks.iter_mut() {
+            track.poll();
+        }
+
+        let grid_height = CELL_SIZE * self.tracks.len() as u32;
+        let (width, height) = (NAME_WIDTH as u32 + CELL_SIZE * MAX_STEPS as u32, grid_height + 120);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+
+        for i in 0..self.tracks.len() {
+            let mut layout = crate::ui_utils::SimpleLayoutBuilder::new(
+                (0, (i as u32 * CELL_SIZE) as i32 + 1), mouse_pos);
+
+            let track = &mut self.tracks[i];
+            let name: String = track.name.chars().take(8).collect();
+            if ui.add_button(&mut canvas, &mut layout, &interact, &name, Some(8)) {
+                self.selected = i;
+            }
+            if ui.add_button(&mut canvas, &mut layout, &interact,
+                    if track.muted { "m" } else { "-" }, Some(1)) {
+                track.muted = !track.muted;
+            }
+        }
+
+        self.draw_grid(&mut canvas, &interact);
+
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, grid_height as i32 + 5), mouse_pos);
+
+        if ui.add_button(&mut canvas, &mut layout, &interact, "+ track", None) {
+            let note = self.tracks.last().map_or(36, |track| track.note.saturating_add(1));
+            self.tracks.push(Track::new(&format!("track {}", self.tracks.len() + 1), note));
+        }
+        if ui.add_button(&mut canvas, &mut layout, &interact, "- track", None) && 1 < self.tracks.len() {
+            self.tracks.pop();
+        }
+        layout.next_row();
+
+        self.selected = self.selected.min(self.tracks.len() - 1);
+        let track = &mut self.tracks[self.selected];
+        let sample = match &track.path {
+            _ if track.loader.is_loading() => "loading...",
+            Some(path) => path.rsplit('/').next().unwrap_or(path),
+            None => "no sample",
+        };
+        ui.add_label(&mut canvas, &mut layout, &format!("{}: note {}, {sample}", track.name, track.note), None);
+        layout.next_row();
+
+        if ui.add_button(&mut canvas, &mut layout, &interact, track.output.as_str(), Some(12)) {
+            track.output = track.output.next();
+        }
+        layout.next_row();
+
+        let mut length = track.length as f32;
+        if ui.add_param(&mut canvas, &mut layout, &interact, "length", &mut length, 1.0, 1.0, MAX_STEPS as f32) {
+            track.length = (length as usize).clamp(1, MAX_STEPS);
+        }
+
+        Some(canvas.into_surface())
+    }
+}
@@ -0,0 +1,276 @@
+use crate::*;
+use std::f32::consts::TAU;
+
+// inharmonic square wave frequencies of the classic metallic hi-hat
+const HAT_FREQS: [f32; 6] = [205.3, 304.4, 369.6, 522.7, 540.0, 800.0];
+const CLAP_BURSTS: usize = 3;
+const CLAP_BURST_SPACING: f32 = 0.01;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DrumKind {
+    Kick,
+    Snare,
+    ClosedHat,
+    OpenHat,
+    Clap,
+}
+
+impl DrumKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Kick => "kick",
+            Self::Snare => "snare",
+            Self::ClosedHat => "closed hat",
+            Self::OpenHat => "open hat",
+            Self::Clap => "clap",
+        }
+    }
+
+    /// General MIDI drum note, used when only reacting to a single note.
+    fn default_note(&self) -> u8 {
+        match self {
+            Self::Kick => 36,
+            Self::Snare => 38,
+            Self::ClosedHat => 42,
+            Self::OpenHat => 46,
+            Self::Clap => 39,
+        }
+    }
+
+    /// Base decay time in seconds.
+    fn decay(&self) -> f32 {
+        match self {
+            Self::Kick => 0.4,
+            Self::Snare => 0.2,
+            Self::ClosedHat => 0.05,
+            Self::OpenHat => 0.4,
+            Self::Clap => 0.25,
+        }
+    }
+
+    fn next(self) -> Self {
+        use DrumKind::*;
+        match self {
+            Kick => Snare,
+            Snare => ClosedHat,
+            ClosedHat => OpenHat,
+            OpenHat => Clap,
+            Clap => Kick,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DrumSynth {
+    kind: DrumKind,
+    tune: f32,
+    decay: f32,
+    tone: f32,
+    level: f32,
+    /// Only trigger on the note of the drum kind instead of any note.
+    filter_note: bool,
+
+    #[serde(skip)]
+    time: Option<f32>,
+    #[serde(skip)]
+    phases: [f32; 6],
+    #[serde(skip)]
+    filters: [Biquad; 2],
+    #[serde(skip)]
+    rng: Rng,
+}
+
+impl DrumSynth {
+    pub fn new() -> Self {
+        Self {
+            kind: DrumKind::Kick,
+            tune: 1.0,
+            decay: 1.0,
+            tone: 0.5,
+            level: 0.8,
+            filter_note: false,
+            time: None,
+            phases: [0.0; 6],
+            filters: Default::default(),
+            rng: Rng::default(),
+        }
+    }
+
+    pub fn set_kind(&mut self, kind: DrumKind) {
+        self.kind = kind;
+        self.time = None;
+    }
+
+    fn trigger(&mut self) {
+        let sample_rate = get_sample_rate() as f32;
+        if sample_rate == 0.0 {
+            return
+        }
+
+        let nyquist = sample_rate * 0.49;
+        self.filters = match self.kind {
+            DrumKind::Snare => [
+                Biquad::highpass(1000.0 * self.tune, 0.7, sample_rate),
+                Biquad::lowpass((8000.0 * self.tune).min(nyquist), 0.7, sample_rate),
+            ],
+            DrumKind::ClosedHat | DrumKind::OpenHat => [
+                Biquad::highpass((7000.0 * self.tune).min(nyquist), 0.7, sample_rate),
+                Biquad::bandpass((10000.0 * self.tune).min(nyquist), 0.8, sample_rate),
+            ],
+            DrumKind::Clap => [
+                Biquad::bandpass(1200.0 * self.tune, 1.5, sample_rate),
+                Biquad::highpass(600.0 * self.tune, 0.7, sample_rate),
+            ],
+            DrumKind::Kick => Default::default(),
+        };
+
+        self.phases = [0.0; 6];
+        self.time = Some(0.0);
+    }
+
+    fn kick(&mut self, time: f32, sample_rate: f32) -> f32 {
+        // the pitch sweeps down from four times the base frequency
+        let base = 50.0 * self.tune;
+        let freq = base + base * 3.0 * (-time / 0.03).exp();
+        self.phases[0] = (self.phases[0] + freq / sample_rate) % 1.0;
+
+        let decay = self.kind.decay() * self.decay;
+        let click = self.rng.next_f32() * (-time / 0.002).exp() * self.tone * 0.3;
+        ((self.phases[0] * TAU).sin() + click) * (-time / decay * 5.0).exp()
+    }
+
+    fn snare(&mut self, time: f32, sample_rate: f32) -> f32 {
+        let mut body = 0.0;
+        for (phase, freq) in self.phases.iter_mut().zip([180.0, 330.0]) {
+            *phase = (*phase + freq * self.tune / sample_rate) % 1.0;
+            body += (*phase * TAU).sin() * 0.5;
+        }
+        let body = body * (-time / 0.05 / self.decay).exp();
+
+        let mut noise = self.rng.next_f32();
+        for filter in self.filters.iter_mut() {
+            noise = filter.process(noise);
+        }
+        let noise = noise * (-time / self.kind.decay() / self.decay * 5.0).exp();
+
+        body * (1.0 - self.tone) + noise * self.tone * 2.0
+    }
+
+    fn hat(&mut self, time: f32, sample_rate: f32) -> f32 {
+        let mut metal = 0.0;
+        for (phase, freq) in self.phases.iter_mut().zip(HAT_FREQS) {
+            *phase = (*phase + freq * self.tune / sample_rate) % 1.0;
+            metal += if *phase < 0.5 { 1.0 } else { -1.0 };
+        }
+        let value = metal / HAT_FREQS.len() as f32 * (1.0 - self.tone)
+            + self.rng.next_f32() * self.tone;
+
+        let mut value = value;
+        for filter in self.filters.iter_mut() {
+            value = filter.process(value);
+        }
+        value * 2.0 * (-time / self.kind.decay() / self.decay * 5.0).exp()
+    }
+
+    fn clap(&mut self, time: f32) -> f32 {
+        // a few short noise bursts followed by a longer tail
+        let bursts = CLAP_BURSTS as f32 * CLAP_BURST_SPACING;
+        let envelope = if time < bursts {
+            (-(time % CLAP_BURST_SPACING) / 0.003).exp()
+        } else {
+            (-(time - bursts) / self.kind.decay() / self.decay * 5.0).exp() * 0.7
+        };
+
+        let mut noise = self.rng.next_f32();
+        for filter in self.filters.iter_mut() {
+            noise = filter.process(noise);
+        }
+        noise * envelope * (1.0 + self.tone * 2.0)
+    }
+}
+
+impl Module for DrumSynth {
+    fn tick(&mut self) -> Option<Data> {
+        let sample_rate = get_sample_rate() as f32;
+        let Some(time) = self.time else {
+            return Some(Data::Audio(0.0))
+        };
+
+        let value = match self.kind {
+            DrumKind::Kick => self.kick(time, sample_rate),
+            DrumKind::Snare => self.snare(time, sample_rate),
+            DrumKind::ClosedHat | DrumKind::OpenHat => self.hat(time, sample_rate),
+            DrumKind::Clap => self.clap(time),
+        };
+
+        // stop once the longest envelope has decayed
+        let time = time + 1.0 / sample_rate;
+        let length = self.kind.decay() * self.decay * 2.0 + CLAP_BURSTS as f32 * CLAP_BURST_SPACING;
+        self.time = if time < length { Some(time) } else { None };
+
+        Some(Data::Audio(value * self.level))
+    }
+
+    define_module! {
+        title: "DrumSynth",
+        id: "drum_synth",
+        output: Audio,
+        inputs: [(Notes, "trigger")],
+    }
+
+    impl_serialization!();
+
+    fn send(&mut self, _input: usize, data: Data) {
+        let notes = data.notes();
+        let key = self.kind.default_note();
+        let triggered = if self.filter_note {
+            notes.iter().any(|note| note.midi() == Some(key))
+        } else {
+            !notes.is_empty()
+        };
+
+        if triggered {
+            self.trigger();
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (260, 160);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        ui.add_label(&mut canvas, &mut layout, self.kind.as_str(), Some(10));
+        if ui.add_button(&mut canvas, &mut layout, &interact, "cycle", None) {
+            self.set_kind(self.kind.next());
+        }
+        layout.next_row();
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "tune", &mut self.tune, 0.05, 0.25, 4.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "decay", &mut self.decay, 0.1, 0.1, 4.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "tone", &mut self.tone, 0.1, 0.0, 1.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "level", &mut self.level, 0.1, 0.0, 1.0);
+
+        let filter = if self.filter_note {
+            format!("note {} only", self.kind.default_note())
+        } else {
+            "any note".to_string()
+        };
+        if ui.add_button(&mut canvas, &mut layout, &interact, &filter, Some(12)) {
+            self.filter_note = !self.filter_note;
+        }
+
+        Some(canvas.into_surface())
+    }
+}
@@ -0,0 +1,98 @@
+use crate::*;
+
+const BAR_WIDTH: u32 = 260;
+const BAR_HEIGHT: u32 = 12;
+
+/// One pole smoothing coefficient reaching about 63% after `time` ms.
+fn coefficient(time: f32, sample_rate: f32) -> f32 {
+    1.0 - (-1000.0 / (time * sample_rate)).exp()
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EnvelopeFollower {
+    /// Attack time in milliseconds.
+    attack: f32,
+    /// Release time in milliseconds.
+    release: f32,
+    gain: f32,
+
+    #[serde(skip)]
+    input: f32,
+    #[serde(skip)]
+    envelope: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn new() -> Self {
+        Self {
+            attack: 5.0,
+            release: 100.0,
+            gain: 1.0,
+            input: 0.0,
+            envelope: 0.0,
+        }
+    }
+}
+
+impl Module for EnvelopeFollower {
+    fn tick(&mut self) -> Option<Data> {
+        let sample_rate = get_sample_rate() as f32;
+        if sample_rate == 0.0 {
+            return Some(Data::Control(0.0))
+        }
+
+        let level = self.input.abs();
+        let time = if self.envelope < level { self.attack } else { self.release };
+        self.envelope += (level - self.envelope) * coefficient(time, sample_rate);
+
+        Some(Data::Control(self.envelope * self.gain))
+    }
+
+    define_module! {
+        title: "EnvelopeFollower",
+        id: "envelope_follower",
+        output: Control,
+        inputs: [(Audio, "audio")],
+    }
+
+    impl_serialization!();
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.audio();
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::{Color, PixelFormatEnum},
+            rect::Rect,
+        };
+
+        let (width, height) = (BAR_WIDTH, BAR_HEIGHT + 90);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        canvas.set_draw_color(Color::RGB(230, 230, 230));
+        canvas.fill_rect(Rect::new(0, 0, BAR_WIDTH, BAR_HEIGHT)).unwrap();
+        let value = (self.envelope * self.gain).abs().min(1.0);
+        if 0.0 < value {
+            canvas.set_draw_color(Color::RGB(0, 0, 200));
+            canvas.fill_rect(Rect::new(0, 0, (value * BAR_WIDTH as f32).max(1.0) as u32, BAR_HEIGHT)).unwrap();
+        }
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_rect(Rect::new(0, 0, BAR_WIDTH, BAR_HEIGHT)).unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, BAR_HEIGHT as i32 + 5), mouse_pos);
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "attack ms", &mut self.attack, 1.0, 1.0, 200.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "release ms", &mut self.release, 10.0, 10.0, 2000.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "gain", &mut self.gain, 0.1, -10.0, 10.0);
+
+        Some(canvas.into_surface())
+    }
+}
@@ -0,0 +1,385 @@
+use crate::*;
+
+const MIN_FREQ: f32 = 20.0;
+const MAX_FREQ: f32 = 20000.0;
+const MAX_GAIN: f32 = 18.0;
+const SPECTRUM_SIZE: usize = 2048;
+
+const GRAPH_WIDTH: u32 = 400;
+const GRAPH_HEIGHT: u32 = 180;
+const HANDLE_SIZE: u32 = 8;
+/// Change of the frequency modulation in octaves before the filters are
+/// recalculated.
+const MODULATION_STEP: f32 = 0.01;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BandType {
+    LowCut,
+    LowShelf,
+    Peak,
+    HighShelf,
+    HighCut,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Band {
+    band_type: BandType,
+    freq: f32,
+    gain: f32,
+    q: f32,
+    enabled: bool,
+    #[serde(skip)]
+    filter: Biquad,
+}
+
+impl Band {
+    fn new(band_type: BandType, freq: f32) -> Self {
+        Self {
+            band_type,
+            freq,
+            gain: 0.0,
+            q: 0.71,
+            enabled: true,
+            filter: Biquad::default(),
+        }
+    }
+
+    /// Filter for the band with its frequency shifted by `octaves`.
+    fn design(&self, sample_rate: f32, octaves: f32) -> Biquad {
+        // keep the frequency below nyquist, the coefficients blow up otherwise
+        let freq = (self.freq * 2.0_f32.powf(octaves)).clamp(1.0, sample_rate * 0.49);
+        match self.band_type {
+            BandType::LowCut => Biquad::highpass(freq, self.q, sample_rate),
+            BandType::LowShelf => Biquad::low_shelf(freq, self.q, self.gain, sample_rate),
+            BandType::Peak => Biquad::peak(freq, self.q, self.gain, sample_rate),
+            BandType::HighShelf => Biquad::high_shelf(freq, self.q, self.gain, sample_rate),
+            BandType::HighCut => Biquad::lowpass(freq, self.q, sample_rate),
+        }
+    }
+
+    fn has_gain(&self) -> bool {
+        !matches!(self.band_type, BandType::LowCut | BandType::HighCut)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Equalizer {
+    bands: Vec<Band>,
+    selected: usize,
+
+    #[serde(skip)]
+    input: f32,
+    /// Frequency shift of all bands in octaves, from the control input.
+    #[serde(skip)]
+    modulation: f32,
+    #[serde(skip)]
+    filter_modulation: f32,
+    #[serde(skip)]
+    filter_rate: u32,
+    #[serde(skip)]
+    dragging: Option<usize>,
+    #[serde(skip)]
+    history: Vec<f32>,
+    #[serde(skip)]
+    history_index: usize,
+}
+
+fn freq_to_x(freq: f32) -> i32 {
+    ((freq / MIN_FREQ).ln() / (MAX_FREQ / MIN_FREQ).ln() * GRAPH_WIDTH as f32) as i32
+}
+
+fn x_to_freq(x: i32) -> f32 {
+    MIN_FREQ * (MAX_FREQ / MIN_FREQ).powf(x as f32 / GRAPH_WIDTH as f32)
+}
+
+fn gain_to_y(gain: f32) -> i32 {
+    let half = GRAPH_HEIGHT as f32 / 2.0;
+    (half - gain / MAX_GAIN * half) as i32
+}
+
+fn y_to_gain(y: i32) -> f32 {
+    let half = GRAPH_HEIGHT as f32 / 2.0;
+    (half - y as f32) / half * MAX_GAIN
+}
+
+impl Equalizer {
+    pub fn new() -> Self {
+        let mut low_cut = Band::new(BandType::LowCut, 30.0);
+        let mut high_cut = Band::new(BandType::HighCut, 18000.0);
+        low_cut.enabled = false;
+        high_cut.enabled = false;
+
+        Self {
+            bands: vec![
+                low_cut,
+                Band::new(BandType::LowShelf, 100.0),
+                Band::new(BandType::Peak, 1000.0),
+                Band::new(BandType::HighShelf, 8000.0),
+                high_cut,
+            ],
+            selected: 2,
+            input: 0.0,
+            modulation: 0.0,
+            filter_modulation: 0.0,
+            filter_rate: 0,
+            dragging: None,
+            history: Vec::new(),
+            history_index: 0,
+        }
+    }
+
+    /// Recalculate the band coefficients, called whenever a parameter or the
+    /// sample rate changes.
+    fn update_filters(&mut self) {
+        let sample_rate = get_sample_rate();
+        for band in self.bands.iter_mut() {
+            let design = band.design(sample_rate as f32, self.modulation);
+            band.filter.set_coefficients(design);
+        }
+        self.filter_rate = sample_rate;
+        self.filter_modulation = self.modulation;
+    }
+
+    fn response_db(&self, freq: f32) -> f32 {
+        let sample_rate = get_sample_rate().max(1) as f32;
+        self.bands.iter()
+            .filter(|band| band.enabled)
+            .map(|band| band.design(sample_rate, self.filter_modulation).response_db(freq, sample_rate))
+            .sum()
+    }
+
+    fn handle_pos(&self, band: &Band) -> (i32, i32) {
+        let gain = if band.has_gain() { band.gain } else { 0.0 };
+        (freq_to_x(band.freq), gain_to_y(gain))
+    }
+
+    fn interact(&mut self, info: &ModuleInteractInfo) -> bool {
+        use sdl2::mouse::MouseButton;
+
+        let (x, y) = (info.x as i32, info.y as i32);
+        if GRAPH_HEIGHT as i32 <= y && self.dragging.is_none() {
+            return false
+        }
+
+        if info.click == Some(MouseButton::Left) {
+            // pick the closest handle
+            self.dragging = self.bands.iter().enumerate()
+                .map(|(i, band)| {
+                    let (hx, hy) = self.handle_pos(band);
+                    (i, (hx - x).pow(2) + (hy - y).pow(2))
+                })
+                .filter(|(_, dist)| *dist < (HANDLE_SIZE as i32 * 2).pow(2))
+                .min_by_key(|(_, dist)| *dist)
+                .map(|(i, _)| i);
+
+            if let Some(i) = self.dragging {
+                self.selected = i;
+            }
+        }
+
+        if !info.event_pump.mouse_state().left() {
+            self.dragging = None;
+        }
+
+        if let Some(i) = self.dragging {
+            let band = &mut self.bands[i];
+            band.freq = x_to_freq(x.clamp(0, GRAPH_WIDTH as i32)).round();
+            if band.has_gain() {
+                band.gain = (y_to_gain(y) * 10.0).round().clamp(-MAX_GAIN * 10.0, MAX_GAIN * 10.0) / 10.0;
+            }
+            return true
+        }
+
+        false
+    }
+
+    fn draw_graph(&self, canvas: &mut sdl2::render::SurfaceCanvas) {
+        use sdl2::{
+            pixels::Color,
+            rect::{Point, Rect},
+        };
+
+        canvas.set_draw_color(Color::RGB(230, 230, 230));
+        canvas.fill_rect(Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT)).unwrap();
+
+        // grid lines at decades and every 6 dB
+        canvas.set_draw_color(Color::RGB(190, 190, 190));
+        for freq in [100.0, 1000.0, 10000.0] {
+            let x = freq_to_x(freq);
+            canvas.draw_line((x, 0), (x, GRAPH_HEIGHT as i32)).unwrap();
+        }
+        for gain in [-12.0, -6.0, 0.0, 6.0, 12.0] {
+            let y = gain_to_y(gain);
+            canvas.draw_line((0, y), (GRAPH_WIDTH as i32, y)).unwrap();
+        }
+
+        // spectrum of the input signal behind the curve
+        if self.history.len() == SPECTRUM_SIZE {
+            let samples: Vec<f32> = (0..SPECTRUM_SIZE)
+                .map(|i| self.history[(self.history_index + i) % SPECTRUM_SIZE])
+                .collect();
+            let spectrum = magnitude_spectrum(&samples);
+            let bin_width = get_sample_rate().max(1) as f32 / SPECTRUM_SIZE as f32;
+
+            canvas.set_draw_color(Color::RGB(150, 170, 200));
+            for x in 0..GRAPH_WIDTH as i32 {
+                let bin = (x_to_freq(x) / bin_width) as usize;
+                if let Some(magnitude) = spectrum.get(bin) {
+                    // -72 dB at the bottom, 0 dB at the top
+                    let db = 20.0 * magnitude.max(1e-6).log10();
+                    let y = (-db / 72.0 * GRAPH_HEIGHT as f32) as i32;
+                    canvas.draw_line((x, y.max(0)), (x, GRAPH_HEIGHT as i32)).unwrap();
+                }
+            }
+        }
+
+        let points: Vec<Point> = (0..GRAPH_WIDTH as i32)
+            .map(|x| Point::new(x, gain_to_y(self.response_db(x_to_freq(x)))))
+            .collect();
+        canvas.set_draw_color(Color::RGB(0, 0, 200));
+        canvas.draw_lines(&points[..]).unwrap();
+
+        for (i, band) in self.bands.iter().enumerate() {
+            let (x, y) = self.handle_pos(band);
+            let rect = Rect::from_center((x, y), HANDLE_SIZE, HANDLE_SIZE);
+            canvas.set_draw_color(if band.enabled {
+                Color::RGB(200, 0, 0)
+            } else {
+                Color::RGB(120, 120, 120)
+            });
+            if i == self.selected {
+                canvas.fill_rect(rect).unwrap();
+            } else {
+                canvas.draw_rect(rect).unwrap();
+            }
+        }
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_rect(Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT)).unwrap();
+    }
+}
+
+impl Module for Equalizer {
+    fn tick(&mut self) -> Option<Data> {
+        // small changes of the modulation aren't worth recalculating for
+        if self.filter_rate != get_sample_rate()
+                || MODULATION_STEP < (self.modulation - self.filter_modulation).abs() {
+            self.update_filters();
+        }
+
+        if self.history.len() < SPECTRUM_SIZE {
+            self.history.push(self.input);
+        } else {
+            self.history[self.history_index] = self.input;
+            self.history_index = (self.history_index + 1) % SPECTRUM_SIZE;
+        }
+
+        let mut value = self.input;
+        for band in self.bands.iter_mut().filter(|band| band.enabled) {
+            value = band.filter.process(value);
+        }
+        Some(Data::Audio(value))
+    }
+
+    define_module! {
+        title: "Equalizer",
+        id: "equalizer",
+        output: Audio,
+        inputs: [(Audio, "audio"), (Control, "freq mod")],
+    }
+
+    impl_serialization!();
+
+    fn send(&mut self, input: usize, data: Data) {
+        match input {
+            0 => self.input = data.audio(),
+            _ => self.modulation = data.control(),
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (GRAPH_WIDTH, GRAPH_HEIGHT + 90);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        let mut changed = match &interact {
+            Some(info) => self.interact(info),
+            None => {
+                self.dragging = None;
+                false
+            }
+        };
+
+        self.draw_graph(&mut canvas);
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new(
+            (0, GRAPH_HEIGHT as i32 + 5), mouse_pos);
+
+        if ui.add_button(&mut canvas, &mut layout, &interact, "<", None) {
+            self.selected = (self.selected + self.bands.len() - 1) % self.bands.len();
+        }
+        if ui.add_button(&mut canvas, &mut layout, &interact, ">", None) {
+            self.selected = (self.selected + 1) % self.bands.len();
+        }
+
+        let band = &mut self.bands[self.selected];
+        ui.add_label(&mut canvas, &mut layout, band.band_type.as_str(), Some(10));
+        if ui.add_button(&mut canvas, &mut layout, &interact, "type", None) {
+            band.band_type = band.band_type.next();
+            changed = true;
+        }
+        if ui.add_button(&mut canvas, &mut layout, &interact,
+                if band.enabled { "on" } else { "off" }, Some(3)) {
+            band.enabled = !band.enabled;
+            band.filter.reset();
+        }
+        layout.next_row();
+
+        ui.add_label(&mut canvas, &mut layout, &format!("{:.0} Hz", band.freq), Some(9));
+        if band.has_gain() {
+            ui.add_label(&mut canvas, &mut layout, &format!("{:+.1} dB", band.gain), Some(8));
+        }
+        layout.next_row();
+
+        changed |= ui.add_param(&mut canvas, &mut layout, &interact, "q", &mut band.q, 0.1, 0.1, 10.0);
+
+        if changed {
+            self.update_filters();
+        }
+
+        Some(canvas.into_surface())
+    }
+}
+
+impl BandType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::LowCut => "low cut",
+            Self::LowShelf => "low shelf",
+            Self::Peak => "peak",
+            Self::HighShelf => "high shelf",
+            Self::HighCut => "high cut",
+        }
+    }
+
+    fn next(self) -> Self {
+        use BandType::*;
+        match self {
+            LowCut => LowShelf,
+            LowShelf => Peak,
+            Peak => HighShelf,
+            HighShelf => HighCut,
+            HighCut => LowCut,
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use crate::*;
+
+use std::{
+    sync::{Arc, atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering}},
+    time::Duration,
+};
+
+// how far ahead of the transport the stream thread decodes, in samples
+const CACHE_SIZE: usize = 1 << 17;
+const READ_CHUNK: usize = 4096;
+const FILL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Decoded audio shared between the audio callback and the stream thread.
+///
+/// The cache is indexed by the (unlooped) playback time in engine samples and
+/// holds the samples for `window_start..window_end`. The audio callback only
+/// publishes the time it wants through `wanted` and reads from the cache, so
+/// it never blocks on or waits for file IO.
+struct Stream {
+    cache: Box<[AtomicU32]>,
+    window_start: AtomicU64,
+    window_end: AtomicU64,
+    wanted: AtomicU64,
+    /// Bumped when the loop region changes and the cache has to be refilled.
+    generation: AtomicU32,
+    loop_start: AtomicU64,
+    loop_end: AtomicU64,
+    stop: AtomicBool,
+}
+
+impl Stream {
+    fn new() -> Self {
+        Self {
+            cache: (0..CACHE_SIZE).map(|_| AtomicU32::new(0)).collect(),
+            window_start: AtomicU64::new(0),
+            window_end: AtomicU64::new(0),
+            wanted: AtomicU64::new(0),
+            generation: AtomicU32::new(0),
+            loop_start: AtomicU64::new(0),
+            loop_end: AtomicU64::new(0),
+            stop: AtomicBool::new(false),
+        }
+    }
+
+    /// Position in the file (in engine samples) for a playback time.
+    fn file_position(&self, time: u64) -> u64 {
+        let loop_start = self.loop_start.load(Ordering::Relaxed);
+        let loop_end = self.loop_end.load(Ordering::Relaxed);
+        if loop_start < loop_end && loop_end <= time {
+            loop_start + (time - loop_start) % (loop_end - loop_start)
+        } else {
+            time
+        }
+    }
+
+    fn read(&self, time: u64) -> f32 {
+        self.wanted.store(time, Ordering::Relaxed);
+        let start = self.window_start.load(Ordering::Acquire);
+        let end = self.window_end.load(Ordering::Acquire);
+        if start <= time && time < end {
+            f32::from_bits(self.cache[time as usize % CACHE_SIZE].load(Ordering::Relaxed))
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Mono frames read from the file around the current read position.
+struct Chunk {
+    reader: AudioFileReader,
+    samples: Vec<f32>,
+    start: usize,
+    interleaved: Vec<f32>,
+}
+
+impl Chunk {
+    /// The frame at `frame` and the one after it, for interpolation.
+    fn frames(&mut self, frame: usize) -> anyhow::Result<(f32, f32)> {
+        let end = self.start + self.samples.len();
+        if frame < self.start || end <= frame + 1 {
+            if self.start <= frame && frame <= end && self.reader.position() == end {
+                // keep reading where the chunk ends, the frames from `frame`
+                // on stay for the interpolation
+                self.samples.drain(..frame - self.start);
+            } else {
+                self.reader.seek(frame)?;
+                self.samples.clear();
+            }
+            self.start = frame;
+
+            let channels = self.reader.channels();
+            self.interleaved.clear();
+            self.reader.read_frames(&mut self.interleaved, READ_CHUNK)?;
+            self.samples.extend(self.interleaved.chunks_exact(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32));
+        }
+
+        let sample = |index: usize| self.samples.get(index).copied().unwrap_or(0.0);
+        Ok((sample(frame - self.start), sample(frame + 1 - self.start)))
+    }
+}
+
+fn stream_thread(stream: Arc<Stream>, reader: AudioFileReader) {
+    let file_rate = reader.sample_rate() as f64;
+    let mut chunk = Chunk {
+        reader,
+        samples: Vec::new(),
+        start: 0,
+        interleaved: Vec::new(),
+    };
+
+    let mut sample_rate = 0;
+    let mut generation = stream.generation.load(Ordering::Acquire);
+
+    while !stream.stop.load(Ordering::Relaxed) {
+        let wanted = stream.wanted.load(Ordering::Relaxed);
+        let mut start = stream.window_start.load(Ordering::Relaxed);
+        let mut end = stream.window_end.load(Ordering::Relaxed);
+
+        let new_generation = stream.generation.load(Ordering::Acquire);
+        if sample_rate != get_sample_rate() || generation != new_generation
+                || wanted < start || end < wanted {
+            // seeked or changed, start over from the wanted position
+            sample_rate = get_sample_rate();
+            generation = new_generation;
+            stream.window_end.store(wanted, Ordering::Release);
+            stream.window_start.store(wanted, Ordering::Release);
+            (start, end) = (wanted, wanted);
+        } else if start < wanted {
+            stream.window_start.store(wanted, Ordering::Release);
+            start = wanted;
+        }
+
+        if sample_rate == 0 {
+            std::thread::sleep(FILL_INTERVAL);
+            continue
+        }
+
+        let ratio = file_rate / sample_rate as f64;
+        let target = (start + CACHE_SIZE as u64).min(end + READ_CHUNK as u64);
+        while end < target {
+            // linear interpolation between the two closest file frames
+            let position = stream.file_position(end) as f64 * ratio;
+            let frame = position as usize;
+            let frac = (position - frame as f64) as f32;
+            let value = match chunk.frames(frame) {
+                Ok((a, b)) => a + (b - a) * frac,
+                Err(err) => {
+                    eprintln!("streaming audio file failed: {err}");
+                    return
+                }
+            };
+
+            stream.cache[end as usize % CACHE_SIZE].store(value.to_bits(), Ordering::Relaxed);
+            end += 1;
+        }
+        stream.window_end.store(end, Ordering::Release);
+
+        if end == start + CACHE_SIZE as u64 {
+            std::thread::sleep(FILL_INTERVAL);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FilePlayer {
+    path: Option<String>,
+    gain: f32,
+    loop_enabled: bool,
+    /// Loop region in bars from the start of the file.
+    loop_start: f32,
+    loop_end: f32,
+    /// Transport position at which the file starts playing.
+    offset: u64,
+
+    #[serde(skip)]
+    stream: Option<Arc<Stream>>,
+    #[serde(skip)]
+    length: f64,
+    #[serde(skip)]
+    time: u64,
+}
+
+impl FilePlayer {
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            gain: 1.0,
+            loop_enabled: false,
+            loop_start: 0.0,
+            loop_end: 4.0,
+            offset: 0,
+            stream: None,
+            length: 0.0,
+            time: 0,
+        }
+    }
+
+    /// Open the file and start streaming it on a separate thread.
+    pub fn load(&mut self, path: &str) -> anyhow::Result<()> {
+        let reader = AudioFileReader::open(path)?;
+        self.stop_stream();
+
+        self.length = reader.frames() as f64 / reader.sample_rate() as f64;
+        let stream = Arc::new(Stream::new());
+        let thread_stream = Arc::clone(&stream);
+        std::thread::spawn(move || stream_thread(thread_stream, reader));
+
+        self.stream = Some(stream);
+        self.path = Some(path.to_string());
+        Ok(())
+    }
+
+    fn stop_stream(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            stream.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Start playing from `seconds` into the file at the current transport position.
+    fn seek(&mut self, seconds: f64) {
+        let samples = (seconds * get_sample_rate() as f64) as u64;
+        self.offset = get_transport_position().saturating_sub(samples);
+    }
+
+    fn update_loop(&self, stream: &Stream) {
+        let (start, end) = if self.loop_enabled {
+            let bar = samples_per_bar() as f32;
+            ((self.loop_start * bar) as u64, (self.loop_end * bar) as u64)
+        } else {
+            (0, 0)
+        };
+
+        if stream.loop_start.load(Ordering::Relaxed) != start
+                || stream.loop_end.load(Ordering::Relaxed) != end {
+            stream.loop_start.store(start, Ordering::Relaxed);
+            stream.loop_end.store(end, Ordering::Relaxed);
+            stream.generation.fetch_add(1, Ordering::Release);
+        }
+    }
+}
+
+impl Drop for FilePlayer {
+    fn drop(&mut self) {
+        self.stop_stream();
+    }
+}
+
+impl Module for FilePlayer {
+    fn tick(&mut self) -> Option<Data> {
+        let Some(stream) = &self.stream else {
+            return Some(Data::Audio(0.0))
+        };
+
+        self.update_loop(stream);
+
+        let transport = get_transport_position();
+        if transport < self.offset {
+            return Some(Data::Audio(0.0))
+        }
+
+        self.time = transport - self.offset;
+        Some(Data::Audio(stream.read(self.time) * self.gain))
+    }
+
+    define_module! {
+        title: "FilePlayer",
+        id: "file_player",
+        output: Audio,
+        inputs: [],
+    }
+
+    impl_serialization!();
+
+    fn loaded(&mut self) {
+        if let Some(path) = self.path.clone()
+                && let Err(err) = self.load(&path) {
+            eprintln!("loading audio file '{path}' failed: {err}");
+        }
+    }
+
+    fn execute(&mut self, cmd: String) {
+        let mut args = cmd.splitn(2, ' ');
+        match (args.next(), args.next().map(str::trim)) {
+            (Some("load"), Some(path)) => match self.load(path) {
+                Ok(()) => println!("streaming {path} ({:.1}s)", self.length),
+                Err(err) => println!("loading {path} failed: {err}"),
+            }
+            (Some("seek"), Some(seconds)) => match seconds.parse() {
+                Ok(seconds) => self.seek(seconds),
+                Err(err) => println!("invalid time: {err}"),
+            }
+            _ => println!("commands: load <file.wav|flac|ogg>, seek <seconds>"),
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::{Color, PixelFormatEnum},
+            rect::Rect,
+        };
+
+        const BAR_HEIGHT: u32 = 20;
+
+        let (width, height) = (300, BAR_HEIGHT + 150);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        // progress bar with the loop region
+        let sample_rate = get_sample_rate().max(1) as f64;
+        let length = self.length.max(f64::EPSILON);
+        let x_of = |seconds: f64| ((seconds / length).clamp(0.0, 1.0) * width as f64) as i32;
+
+        canvas.set_draw_color(Color::RGB(230, 230, 230));
+        canvas.fill_rect(Rect::new(0, 0, width, BAR_HEIGHT)).unwrap();
+
+        let bar_seconds = samples_per_bar() as f64 / sample_rate;
+        if self.loop_enabled {
+            let start = x_of(self.loop_start as f64 * bar_seconds);
+            let end = x_of(self.loop_end as f64 * bar_seconds);
+            canvas.set_draw_color(Color::RGB(200, 220, 200));
+            canvas.fill_rect(Rect::new(start, 0, (end - start).max(1) as u32, BAR_HEIGHT)).unwrap();
+        }
+
+        let position = match &self.stream {
+            Some(stream) => stream.file_position(self.time) as f64 / sample_rate,
+            None => 0.0,
+        };
+        canvas.set_draw_color(Color::RGB(200, 0, 0));
+        let x = x_of(position).min(width as i32 - 1);
+        canvas.draw_line((x, 0), (x, BAR_HEIGHT as i32)).unwrap();
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_rect(Rect::new(0, 0, width, BAR_HEIGHT)).unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, BAR_HEIGHT as i32 + 5), mouse_pos);
+
+        let name = match &self.path {
+            Some(path) => path.rsplit('/').next().unwrap_or(path),
+            None => "no file loaded",
+        };
+        ui.add_label(&mut canvas, &mut layout, name, None);
+        layout.next_row();
+
+        let format_time = |seconds: f64| format!("{}:{:04.1}", (seconds / 60.0) as u32, seconds % 60.0);
+        ui.add_label(&mut canvas, &mut layout, &format!(
+            "{} / {}", format_time(position), format_time(self.length)), None);
+        if ui.add_button(&mut canvas, &mut layout, &interact, "restart", None) {
+            self.seek(0.0);
+        }
+        layout.next_row();
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "gain", &mut self.gain, 0.05, 0.0, 2.0);
+
+        if ui.add_button(&mut canvas, &mut layout, &interact,
+                if self.loop_enabled { "loop on" } else { "loop off" }, Some(8)) {
+            self.loop_enabled = !self.loop_enabled;
+        }
+        layout.next_row();
+
+        if self.loop_enabled {
+            ui.add_param(&mut canvas, &mut layout, &interact, "loop start", &mut self.loop_start, 1.0, 0.0, 999.0);
+            ui.add_param(&mut canvas, &mut layout, &interact, "loop end", &mut self.loop_end, 1.0, 1.0, 1000.0);
+            self.loop_end = self.loop_end.max(self.loop_start + 1.0);
+        }
+
+        Some(canvas.into_surface())
+    }
+}
@@ -0,0 +1,164 @@
+use crate::*;
+use std::f32::consts::{PI, TAU};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    Idle,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FmOperator {
+    /// Frequency relative to the incoming note.
+    ratio: f32,
+    /// Output level, for a modulator this is the modulation index in radians.
+    level: f32,
+    feedback: f32,
+    /// Envelope times in milliseconds.
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+
+    #[serde(skip)]
+    freq: f32,
+    #[serde(skip)]
+    phase: f32,
+    #[serde(skip)]
+    modulation: f32,
+    /// The last two outputs, averaged for feedback like the DX7 does.
+    #[serde(skip)]
+    previous: [f32; 2],
+    #[serde(skip)]
+    stage: Option<Stage>,
+    #[serde(skip)]
+    envelope: f32,
+    /// Envelope level when the note was released.
+    #[serde(skip)]
+    release_level: f32,
+}
+
+impl FmOperator {
+    pub fn new() -> Self {
+        Self {
+            ratio: 1.0,
+            level: 1.0,
+            feedback: 0.0,
+            attack: 5.0,
+            decay: 200.0,
+            sustain: 0.7,
+            release: 300.0,
+            freq: 0.0,
+            phase: 0.0,
+            modulation: 0.0,
+            previous: [0.0; 2],
+            stage: None,
+            envelope: 0.0,
+            release_level: 0.0,
+        }
+    }
+
+    fn advance_envelope(&mut self, sample_rate: f32) {
+        let step = |time: f32| 1000.0 / (time.max(0.1) * sample_rate);
+
+        match self.stage.unwrap_or(Stage::Idle) {
+            Stage::Attack => {
+                self.envelope += step(self.attack);
+                if 1.0 <= self.envelope {
+                    self.envelope = 1.0;
+                    self.stage = Some(Stage::Decay);
+                }
+            }
+            Stage::Decay => {
+                self.envelope -= step(self.decay) * (1.0 - self.sustain);
+                if self.envelope <= self.sustain {
+                    self.envelope = self.sustain;
+                    self.stage = Some(Stage::Sustain);
+                }
+            }
+            Stage::Sustain => self.envelope = self.sustain,
+            Stage::Release => {
+                self.envelope -= step(self.release) * self.release_level;
+                if self.envelope <= 0.0 {
+                    self.envelope = 0.0;
+                    self.stage = Some(Stage::Idle);
+                }
+            }
+            Stage::Idle => {}
+        }
+    }
+}
+
+impl Module for FmOperator {
+    fn tick(&mut self) -> Option<Data> {
+        let sample_rate = get_sample_rate() as f32;
+        if sample_rate == 0.0 || self.stage.is_none_or(|stage| stage == Stage::Idle) {
+            return Some(Data::Audio(0.0))
+        }
+
+        self.advance_envelope(sample_rate);
+        self.phase = (self.phase + self.freq * self.ratio / sample_rate) % 1.0;
+
+        let feedback = (self.previous[0] + self.previous[1]) / 2.0 * self.feedback * PI;
+        let value = (self.phase * TAU + self.modulation + feedback).sin() * self.envelope;
+        self.previous = [value, self.previous[0]];
+
+        Some(Data::Audio(value * self.level))
+    }
+
+    define_module! {
+        title: "FmOperator",
+        id: "fm_operator",
+        output: Audio,
+        inputs: [(Notes, "notes"), (Audio, "phase mod")],
+    }
+
+    impl_serialization!();
+
+    fn send(&mut self, input: usize, data: Data) {
+        match input {
+            0 => match data.notes().first() {
+                Some(note) => {
+                    self.freq = note.freq();
+                    self.stage = Some(Stage::Attack);
+                }
+                None => if self.stage.is_some() {
+                    self.release_level = self.envelope;
+                    self.stage = Some(Stage::Release);
+                }
+            }
+            _ => self.modulation = data.audio(),
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (260, 190);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "ratio", &mut self.ratio, 0.5, 0.5, 16.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "level", &mut self.level, 0.1, 0.0, 8.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "feedback", &mut self.feedback, 0.05, 0.0, 1.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "attack ms", &mut self.attack, 5.0, 0.0, 2000.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "decay ms", &mut self.decay, 10.0, 0.0, 4000.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "sustain", &mut self.sustain, 0.05, 0.0, 1.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "release ms", &mut self.release, 10.0, 0.0, 4000.0);
+
+        Some(canvas.into_surface())
+    }
+}
@@ -0,0 +1,218 @@
+use crate::*;
+
+const FORMANTS: usize = 3;
+/// Change of the vowel position before the filters are recalculated.
+const MODULATION_STEP: f32 = 0.01;
+
+const GRAPH_WIDTH: u32 = 300;
+const GRAPH_HEIGHT: u32 = 80;
+const GRAPH_MIN_FREQ: f32 = 100.0;
+const GRAPH_MAX_FREQ: f32 = 5000.0;
+const GRAPH_MIN_DB: f32 = -36.0;
+
+struct Vowel {
+    name: &'static str,
+    freqs: [f32; FORMANTS],
+    bandwidths: [f32; FORMANTS],
+    gains: [f32; FORMANTS],
+}
+
+/// Formants of a male voice, in the order the vowel position morphs through.
+const VOWELS: [Vowel; 5] = [
+    Vowel { name: "a", freqs: [600.0, 1040.0, 2250.0], bandwidths: [60.0, 70.0, 110.0], gains: [0.0, -7.0, -9.0] },
+    Vowel { name: "e", freqs: [400.0, 1620.0, 2400.0], bandwidths: [40.0, 80.0, 100.0], gains: [0.0, -12.0, -9.0] },
+    Vowel { name: "i", freqs: [250.0, 1750.0, 2600.0], bandwidths: [60.0, 90.0, 100.0], gains: [0.0, -30.0, -16.0] },
+    Vowel { name: "o", freqs: [400.0, 750.0, 2400.0], bandwidths: [40.0, 80.0, 100.0], gains: [0.0, -11.0, -21.0] },
+    Vowel { name: "u", freqs: [350.0, 600.0, 2400.0], bandwidths: [40.0, 80.0, 100.0], gains: [0.0, -20.0, -32.0] },
+];
+
+#[derive(Clone, Copy)]
+struct Formant {
+    freq: f32,
+    bandwidth: f32,
+    gain_db: f32,
+}
+
+/// Formants between the vowels around `position`, frequencies interpolated
+/// in octaves.
+fn formants(position: f32) -> [Formant; FORMANTS] {
+    let position = position.clamp(0.0, (VOWELS.len() - 1) as f32);
+    let index = (position as usize).min(VOWELS.len() - 2);
+    let frac = position - index as f32;
+    let (a, b) = (&VOWELS[index], &VOWELS[index + 1]);
+
+    std::array::from_fn(|i| Formant {
+        freq: a.freqs[i] * (b.freqs[i] / a.freqs[i]).powf(frac),
+        bandwidth: a.bandwidths[i] + (b.bandwidths[i] - a.bandwidths[i]) * frac,
+        gain_db: a.gains[i] + (b.gains[i] - a.gains[i]) * frac,
+    })
+}
+
+/// Parallel bandpass filters at the formants of a vowel, morphing between
+/// the vowels a, e, i, o and u.
+#[derive(Serialize, Deserialize)]
+pub struct FormantFilter {
+    /// Position between the vowels, 0 is "a" and 4 is "u".
+    vowel: f32,
+    /// Shift of all formants in semitones.
+    shift: f32,
+    /// Multiplier for the bandwidths, higher values sound less resonant.
+    width: f32,
+    mix: f32,
+
+    #[serde(skip)]
+    input: f32,
+    /// Offset of the vowel position from the control input.
+    #[serde(skip)]
+    modulation: f32,
+    #[serde(skip)]
+    filters: [Biquad; FORMANTS],
+    #[serde(skip)]
+    gains: [f32; FORMANTS],
+    /// Sample rate and parameters the filters were designed for.
+    #[serde(skip)]
+    design: Option<(u32, f32, f32, f32)>,
+}
+
+impl FormantFilter {
+    pub fn new() -> Self {
+        Self {
+            vowel: 0.0,
+            shift: 0.0,
+            width: 1.0,
+            mix: 1.0,
+            input: 0.0,
+            modulation: 0.0,
+            filters: [Biquad::default(); FORMANTS],
+            gains: [0.0; FORMANTS],
+            design: None,
+        }
+    }
+
+    fn position(&self) -> f32 {
+        (self.vowel + self.modulation).clamp(0.0, (VOWELS.len() - 1) as f32)
+    }
+
+    fn current_formants(&self) -> [Formant; FORMANTS] {
+        let shift = 2.0_f32.powf(self.shift / 12.0);
+        formants(self.position()).map(|formant| Formant {
+            freq: formant.freq * shift,
+            bandwidth: formant.bandwidth * shift * self.width,
+            ..formant
+        })
+    }
+
+    fn update_filters(&mut self, sample_rate: u32) {
+        let design = Some((sample_rate, self.position(), self.shift, self.width));
+        if self.design == design {
+            return
+        }
+        self.design = design;
+
+        let sr = sample_rate as f32;
+        for (i, formant) in self.current_formants().iter().enumerate() {
+            let freq = formant.freq.clamp(1.0, sr * 0.49);
+            let q = formant.freq / formant.bandwidth.max(1.0);
+            self.filters[i].set_coefficients(Biquad::bandpass(freq, q, sr));
+            self.gains[i] = 10.0_f32.powf(formant.gain_db / 20.0);
+        }
+    }
+}
+
+impl Module for FormantFilter {
+    fn tick(&mut self) -> Option<Data> {
+        let sample_rate = get_sample_rate();
+        if sample_rate == 0 {
+            return Some(Data::Audio(0.0))
+        }
+        self.update_filters(sample_rate);
+
+        let mut filtered = 0.0;
+        for (filter, gain) in self.filters.iter_mut().zip(self.gains) {
+            filtered += filter.process(self.input) * gain;
+        }
+
+        Some(Data::Audio(self.input + (filtered - self.input) * self.mix))
+    }
+
+    define_module! {
+        title: "FormantFilter",
+        id: "formant_filter",
+        output: Audio,
+        inputs: [(Audio, "input"), (Control, "vowel")],
+    }
+
+    impl_serialization!();
+
+    fn send(&mut self, input: usize, data: Data) {
+        match input {
+            0 => self.input = data.audio(),
+            _ => {
+                let modulation = data.control();
+                if MODULATION_STEP <= (modulation - self.modulation).abs() {
+                    self.modulation = modulation;
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::{Color, PixelFormatEnum},
+            rect::Rect,
+        };
+
+        let (width, height) = (GRAPH_WIDTH, GRAPH_HEIGHT + 130);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        // formant peaks on a log frequency axis
+        canvas.set_draw_color(Color::RGB(230, 230, 230));
+        canvas.fill_rect(Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT)).unwrap();
+
+        let x_of = |freq: f32| {
+            let fraction = (freq / GRAPH_MIN_FREQ).log2() / (GRAPH_MAX_FREQ / GRAPH_MIN_FREQ).log2();
+            (fraction.clamp(0.0, 1.0) * GRAPH_WIDTH as f32) as i32
+        };
+
+        canvas.set_draw_color(Color::RGB(190, 190, 190));
+        for freq in [200.0, 500.0, 1000.0, 2000.0] {
+            let x = x_of(freq);
+            canvas.draw_line((x, 0), (x, GRAPH_HEIGHT as i32)).unwrap();
+        }
+
+        canvas.set_draw_color(Color::RGB(0, 0, 200));
+        for formant in self.current_formants() {
+            let fraction = ((formant.gain_db - GRAPH_MIN_DB) / -GRAPH_MIN_DB).clamp(0.0, 1.0);
+            let top = GRAPH_HEIGHT as i32 - (fraction * GRAPH_HEIGHT as f32) as i32;
+            let left = x_of(formant.freq - formant.bandwidth / 2.0);
+            let right = x_of(formant.freq + formant.bandwidth / 2.0);
+            canvas.fill_rect(Rect::new(left, top, (right - left).max(2) as u32, GRAPH_HEIGHT - top as u32)).unwrap();
+        }
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_rect(Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT)).unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, GRAPH_HEIGHT as i32 + 5), mouse_pos);
+
+        let position = self.position();
+        let index = (position as usize).min(VOWELS.len() - 2);
+        let frac = position - index as f32;
+        ui.add_label(&mut canvas, &mut layout, &format!(
+            "{} > {} {:.0}%", VOWELS[index].name, VOWELS[index + 1].name, frac * 100.0), None);
+        layout.next_row();
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "vowel", &mut self.vowel, 0.1, 0.0, (VOWELS.len() - 1) as f32);
+        ui.add_param(&mut canvas, &mut layout, &interact, "shift", &mut self.shift, 1.0, -12.0, 12.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "width", &mut self.width, 0.1, 0.5, 4.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "mix", &mut self.mix, 0.05, 0.0, 1.0);
+
+        Some(canvas.into_surface())
+    }
+}
@@ -0,0 +1,353 @@
+use crate::*;
+
+const MAX_GRAINS: usize = 64;
+/// Length of the live input buffer in seconds.
+const LIVE_SECONDS: usize = 4;
+const WAVEFORM_HEIGHT: u32 = 100;
+/// Rows the grains are spread over in the view.
+const CLOUD_ROWS: u32 = 12;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GrainSource {
+    Sample,
+    /// The last few seconds of the audio input.
+    Live,
+}
+
+impl GrainSource {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Sample => "sample",
+            Self::Live => "live",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Self::Sample => Self::Live,
+            Self::Live => Self::Sample,
+        }
+    }
+}
+
+struct Grain {
+    /// Read position, for the live buffer counted in samples written so far.
+    position: f64,
+    step: f64,
+    age: usize,
+    length: usize,
+    row: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Granular {
+    path: Option<String>,
+    source: GrainSource,
+    /// Center of the grain positions as a fraction of the buffer.
+    position: f32,
+    /// Grain length in milliseconds.
+    size: f32,
+    /// Grains started per second.
+    density: f32,
+    /// Transposition in semitones.
+    pitch: f32,
+    /// Random deviation of the grain positions as a fraction of the buffer.
+    spray: f32,
+    window: Window,
+    level: f32,
+
+    // the file as loaded and resampled to the engine sample rate
+    #[serde(skip)]
+    file: AudioBuffer,
+    #[serde(skip)]
+    sample: AudioBuffer,
+    #[serde(skip)]
+    live: Vec<f32>,
+    #[serde(skip)]
+    written: u64,
+    #[serde(skip)]
+    input: f32,
+    #[serde(skip)]
+    grains: Vec<Grain>,
+    /// Samples until the next grain starts.
+    #[serde(skip)]
+    countdown: f32,
+    #[serde(skip)]
+    rng: Rng,
+}
+
+impl Granular {
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            source: GrainSource::Live,
+            position: 0.5,
+            size: 80.0,
+            density: 20.0,
+            pitch: 0.0,
+            spray: 0.05,
+            window: Window::Hann,
+            level: 0.5,
+            file: AudioBuffer::default(),
+            sample: AudioBuffer::default(),
+            live: Vec::new(),
+            written: 0,
+            input: 0.0,
+            grains: Vec::new(),
+            countdown: 0.0,
+            rng: Rng::default(),
+        }
+    }
+
+    pub fn load(&mut self, path: &str) -> anyhow::Result<()> {
+        self.file = AudioBuffer::load_wav(path)?;
+        self.sample = AudioBuffer::default();
+        self.grains.clear();
+        self.path = Some(path.to_string());
+        self.source = GrainSource::Sample;
+        self.prepare(get_sample_rate());
+        Ok(())
+    }
+
+    /// Resample the file and allocate the live buffer and grains for the
+    /// engine sample rate.
+    fn prepare(&mut self, sample_rate: u32) {
+        if sample_rate == 0 {
+            return
+        }
+        if self.sample.sample_rate != sample_rate && !self.file.is_empty() {
+            self.sample = self.file.resampled(sample_rate);
+        }
+        let live_len = sample_rate as usize * LIVE_SECONDS;
+        if self.live.len() != live_len {
+            self.live = vec![0.0; live_len];
+            self.written = 0;
+            self.grains.clear();
+        }
+        self.grains.reserve(MAX_GRAINS);
+    }
+
+    fn buffer_len(&self) -> usize {
+        match self.source {
+            GrainSource::Sample => self.sample.len(),
+            GrainSource::Live => self.live.len(),
+        }
+    }
+
+    fn read(&self, position: f64) -> f32 {
+        match self.source {
+            GrainSource::Sample => self.sample.read(position),
+            GrainSource::Live => {
+                let len = self.live.len();
+                let index = position as usize;
+                let frac = (position - index as f64) as f32;
+                let a = self.live[index % len];
+                let b = self.live[(index + 1) % len];
+                a + (b - a) * frac
+            }
+        }
+    }
+
+    fn spawn(&mut self) {
+        let len = self.buffer_len();
+        let length = (self.size / 1000.0 * get_sample_rate() as f32) as usize;
+        if len < 2 || length == 0 || MAX_GRAINS <= self.grains.len() {
+            return
+        }
+
+        let step = 2.0_f64.powf(self.pitch as f64 / 12.0);
+        let center = self.position + self.rng.next_f32() * self.spray;
+        let mut position = center.clamp(0.0, 1.0) as f64 * len as f64;
+
+        if self.source == GrainSource::Live {
+            // keep the whole grain between the oldest sample and the write head
+            let length = length as f64;
+            let oldest = self.written as f64 - len as f64 + 1.0 + length * (1.0 - step).max(0.0);
+            let newest = self.written as f64 - 2.0 - length * (step - 1.0).max(0.0);
+            if newest < oldest {
+                return
+            }
+            position = (oldest + position).min(newest);
+        } else if len as f64 <= position + length as f64 * step {
+            position = (len as f64 - length as f64 * step - 1.0).max(0.0);
+        }
+
+        let row = self.rng.next_u32() % CLOUD_ROWS;
+        self.grains.push(Grain { position, step, age: 0, length, row });
+    }
+
+    /// Fraction of the buffer a grain is at, for drawing.
+    fn grain_fraction(&self, grain: &Grain) -> f64 {
+        let len = self.buffer_len().max(1) as f64;
+        match self.source {
+            GrainSource::Sample => grain.position / len,
+            GrainSource::Live => (grain.position - self.written as f64 + len) / len,
+        }
+    }
+}
+
+impl Module for Granular {
+    fn tick(&mut self) -> Option<Data> {
+        let sample_rate = get_sample_rate() as f32;
+        if sample_rate == 0.0 || self.live.is_empty() {
+            return Some(Data::Audio(0.0))
+        }
+
+        let len = self.live.len();
+        self.live[self.written as usize % len] = self.input;
+        self.written += 1;
+
+        self.countdown -= 1.0;
+        if self.countdown <= 0.0 {
+            self.countdown += sample_rate / self.density.max(0.1);
+            self.spawn();
+        }
+
+        let mut value = 0.0;
+        for grain in self.grains.iter() {
+            value += self.read(grain.position) * self.window.value(grain.age, grain.length);
+        }
+        for grain in self.grains.iter_mut() {
+            grain.position += grain.step;
+            grain.age += 1;
+        }
+        self.grains.retain(|grain| grain.age < grain.length);
+
+        // overlapping grains add up, keep the level about the same
+        let overlap = (self.density * self.size / 1000.0).max(1.0);
+        Some(Data::Audio(value / overlap.sqrt() * self.level))
+    }
+
+    define_module! {
+        title: "Granular",
+        id: "granular",
+        output: Audio,
+        inputs: [(Audio, "input")],
+    }
+
+    impl_serialization!();
+
+    fn loaded(&mut self) {
+        if let Some(path) = self.path.clone() {
+            let source = self.source;
+            if let Err(err) = self.load(&path) {
+                eprintln!("loading sample '{path}' failed: {err}");
+            }
+            self.source = source;
+        }
+    }
+
+    fn sample_rate_changed(&mut self, sample_rate: u32) {
+        self.prepare(sample_rate);
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.audio();
+    }
+
+    fn execute(&mut self, cmd: String) {
+        let mut args = cmd.split_whitespace();
+        match (args.next(), args.next()) {
+            (Some("load"), Some(path)) => match self.load(path) {
+                Ok(()) => println!("loaded {path} ({} samples)", self.file.len()),
+                Err(err) => println!("loading {path} failed: {err}"),
+            }
+            _ => println!("commands: load <file.wav>"),
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::{Color, PixelFormatEnum},
+            mouse::MouseButton,
+            rect::Rect,
+        };
+
+        let (width, height) = (400, WAVEFORM_HEIGHT + 200);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        // clicking the waveform moves the grain position there
+        if let Some(info) = &interact
+                && (info.y as u32) < WAVEFORM_HEIGHT
+                && info.click == Some(MouseButton::Left) {
+            self.position = info.x as f32 / width as f32;
+        }
+
+        let x_of = |fraction: f64| (fraction.clamp(0.0, 1.0) * width as f64) as i32;
+
+        canvas.set_draw_color(Color::RGB(230, 230, 230));
+        canvas.fill_rect(Rect::new(0, 0, width, WAVEFORM_HEIGHT)).unwrap();
+
+        // the range the grains are sprayed over
+        let start = x_of((self.position - self.spray) as f64);
+        let end = x_of((self.position + self.spray) as f64);
+        canvas.set_draw_color(Color::RGB(200, 220, 200));
+        canvas.fill_rect(Rect::new(start, 0, (end - start).max(1) as u32, WAVEFORM_HEIGHT)).unwrap();
+
+        canvas.set_draw_color(Color::RGB(0, 0, 200));
+        let rect = Rect::new(0, 0, width, WAVEFORM_HEIGHT);
+        match self.source {
+            GrainSource::Sample => crate::ui_utils::draw_waveform(&mut canvas, rect, &self.file.samples),
+            GrainSource::Live if !self.live.is_empty() => {
+                // oldest to newest
+                let split = self.written as usize % self.live.len();
+                let ordered: Vec<f32> = self.live[split..].iter()
+                    .chain(self.live[..split].iter()).copied().collect();
+                crate::ui_utils::draw_waveform(&mut canvas, rect, &ordered);
+            }
+            GrainSource::Live => {}
+        }
+
+        // grain cloud, fading in and out with the window
+        let row_height = WAVEFORM_HEIGHT / CLOUD_ROWS;
+        for grain in self.grains.iter() {
+            let amplitude = self.window.value(grain.age, grain.length).clamp(0.0, 1.0);
+            let shade = (255.0 * (1.0 - amplitude)) as u8;
+            canvas.set_draw_color(Color::RGB(255, shade, shade));
+            let x = x_of(self.grain_fraction(grain));
+            let y = (grain.row * row_height) as i32;
+            canvas.fill_rect(Rect::new(x - 2, y + 1, 5, row_height.max(3) - 2)).unwrap();
+        }
+
+        canvas.set_draw_color(Color::RGB(200, 0, 0));
+        let x = x_of(self.position as f64).min(width as i32 - 1);
+        canvas.draw_line((x, 0), (x, WAVEFORM_HEIGHT as i32)).unwrap();
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_rect(rect).unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, WAVEFORM_HEIGHT as i32 + 5), mouse_pos);
+
+        let name = match (&self.source, &self.path) {
+            (GrainSource::Live, _) => "live input",
+            (GrainSource::Sample, Some(path)) => path.rsplit('/').next().unwrap_or(path),
+            (GrainSource::Sample, None) => "no sample loaded",
+        };
+        ui.add_label(&mut canvas, &mut layout, name, Some(16));
+        if ui.add_button(&mut canvas, &mut layout, &interact, self.source.as_str(), Some(6)) {
+            self.source = self.source.next();
+            self.grains.clear();
+        }
+        ui.add_label(&mut canvas, &mut layout, self.window.as_str(), Some(11));
+        if ui.add_button(&mut canvas, &mut layout, &interact, "window", None) {
+            self.window = self.window.next();
+        }
+        layout.next_row();
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "position", &mut self.position, 0.01, 0.0, 1.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "size ms", &mut self.size, 5.0, 5.0, 1000.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "density", &mut self.density, 1.0, 1.0, 200.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "pitch", &mut self.pitch, 1.0, -24.0, 24.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "spray", &mut self.spray, 0.01, 0.0, 1.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "level", &mut self.level, 0.05, 0.0, 1.0);
+
+        Some(canvas.into_surface())
+    }
+}
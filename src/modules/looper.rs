@@ -0,0 +1,253 @@
+use crate::*;
+
+const MAX_UNDO: usize = 8;
+const WAVEFORM_HEIGHT: u32 = 80;
+
+#[derive(Clone, Copy, PartialEq)]
+enum LooperState {
+    /// Waiting for the next bar to start recording.
+    Armed,
+    Recording,
+    Playing,
+    Overdubbing,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Looper {
+    buffer: Vec<f32>,
+    /// Length of the next recording in bars.
+    bars: f32,
+
+    #[serde(skip)]
+    state: Option<LooperState>,
+    #[serde(skip)]
+    position: usize,
+    #[serde(skip)]
+    undo: Vec<Vec<f32>>,
+    #[serde(skip)]
+    input: f32,
+}
+
+impl Looper {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            bars: 1.0,
+            state: None,
+            position: 0,
+            undo: Vec::new(),
+            input: 0.0,
+        }
+    }
+
+    fn push_undo(&mut self) {
+        if MAX_UNDO <= self.undo.len() {
+            self.undo.remove(0);
+        }
+        self.undo.push(self.buffer.clone());
+    }
+
+    /// Allocate the buffer up front and wait for the next bar, so the audio
+    /// callback only has to write into it.
+    fn record(&mut self) {
+        if get_sample_rate() == 0 {
+            return
+        }
+        self.push_undo();
+        self.buffer = vec![0.0; (samples_per_bar() as f32 * self.bars) as usize];
+        self.position = 0;
+        self.state = Some(LooperState::Armed);
+    }
+
+    fn toggle_overdub(&mut self) {
+        match self.state {
+            Some(LooperState::Playing) => {
+                self.push_undo();
+                self.state = Some(LooperState::Overdubbing);
+            }
+            Some(LooperState::Overdubbing) => self.state = Some(LooperState::Playing),
+            _ => {}
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some(buffer) = self.undo.pop() {
+            self.buffer = buffer;
+            self.position %= self.buffer.len().max(1);
+            self.state = if self.buffer.is_empty() {
+                None
+            } else {
+                Some(LooperState::Playing)
+            };
+        }
+    }
+
+    fn halve(&mut self) {
+        if 1 < self.buffer.len() && self.state != Some(LooperState::Recording) {
+            self.push_undo();
+            self.buffer.truncate(self.buffer.len() / 2);
+            self.position %= self.buffer.len();
+        }
+    }
+
+    fn double(&mut self) {
+        if !self.buffer.is_empty() && self.state != Some(LooperState::Recording) {
+            self.push_undo();
+            self.buffer.extend_from_within(..);
+        }
+    }
+
+    fn reverse(&mut self) {
+        if self.state != Some(LooperState::Recording) {
+            self.push_undo();
+            self.buffer.reverse();
+            self.position = (self.buffer.len() - self.position) % self.buffer.len().max(1);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.push_undo();
+        self.buffer.clear();
+        self.position = 0;
+        self.state = None;
+    }
+}
+
+impl Module for Looper {
+    fn tick(&mut self) -> Option<Data> {
+        // a loop loaded from a project starts playing in time with the transport
+        if self.state.is_none() && !self.buffer.is_empty() {
+            self.position = (get_transport_position() % self.buffer.len() as u64) as usize;
+            self.state = Some(LooperState::Playing);
+        }
+
+        if self.state == Some(LooperState::Armed)
+                && get_transport_position().is_multiple_of(samples_per_bar().max(1)) {
+            self.state = Some(LooperState::Recording);
+        }
+
+        let output = match self.state {
+            Some(LooperState::Recording) => {
+                self.buffer[self.position] = self.input;
+                self.position += 1;
+                if self.buffer.len() <= self.position {
+                    self.position = 0;
+                    self.state = Some(LooperState::Playing);
+                }
+                0.0
+            }
+            Some(state @ (LooperState::Playing | LooperState::Overdubbing)) => {
+                let value = self.buffer[self.position];
+                if state == LooperState::Overdubbing {
+                    self.buffer[self.position] += self.input;
+                }
+                self.position = (self.position + 1) % self.buffer.len();
+                value
+            }
+            _ => 0.0,
+        };
+
+        Some(Data::Audio(self.input + output))
+    }
+
+    define_module! {
+        title: "Looper",
+        id: "looper",
+        output: Audio,
+        inputs: [(Audio, "audio")],
+    }
+
+    impl_serialization!();
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.audio();
+    }
+
+    fn execute(&mut self, cmd: String) {
+        match cmd.trim() {
+            "record" => self.record(),
+            "overdub" => self.toggle_overdub(),
+            "undo" => self.undo(),
+            "halve" => self.halve(),
+            "double" => self.double(),
+            "reverse" => self.reverse(),
+            "clear" => self.clear(),
+            _ => println!("commands: record, overdub, undo, halve, double, reverse, clear"),
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::{Color, PixelFormatEnum},
+            rect::Rect,
+        };
+
+        let (width, height) = (320, WAVEFORM_HEIGHT + 110);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        canvas.set_draw_color(Color::RGB(230, 230, 230));
+        canvas.fill_rect(Rect::new(0, 0, width, WAVEFORM_HEIGHT)).unwrap();
+
+        let rect = Rect::new(0, 0, width, WAVEFORM_HEIGHT);
+        canvas.set_draw_color(Color::RGB(0, 0, 200));
+        crate::ui_utils::draw_waveform(&mut canvas, rect, &self.buffer);
+
+        if !self.buffer.is_empty() {
+            let x = (self.position * width as usize / self.buffer.len()) as i32;
+            canvas.set_draw_color(Color::RGB(200, 0, 0));
+            canvas.draw_line((x, 0), (x, WAVEFORM_HEIGHT as i32)).unwrap();
+        }
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_rect(rect).unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, WAVEFORM_HEIGHT as i32 + 5), mouse_pos);
+
+        let state = match self.state {
+            None => "empty",
+            Some(LooperState::Armed) => "armed",
+            Some(LooperState::Recording) => "recording",
+            Some(LooperState::Playing) => "playing",
+            Some(LooperState::Overdubbing) => "overdubbing",
+        };
+        let bars = self.buffer.len() as f32 / samples_per_bar().max(1) as f32;
+        ui.add_label(&mut canvas, &mut layout, &format!("{state}, {bars:.2} bars"), None);
+        layout.next_row();
+
+        if ui.add_button(&mut canvas, &mut layout, &interact, "record", None) {
+            self.record();
+        }
+        if ui.add_button(&mut canvas, &mut layout, &interact, "overdub", None) {
+            self.toggle_overdub();
+        }
+        if ui.add_button(&mut canvas, &mut layout, &interact, "undo", None) {
+            self.undo();
+        }
+        if ui.add_button(&mut canvas, &mut layout, &interact, "clear", None) {
+            self.clear();
+        }
+        layout.next_row();
+
+        if ui.add_button(&mut canvas, &mut layout, &interact, "halve", None) {
+            self.halve();
+        }
+        if ui.add_button(&mut canvas, &mut layout, &interact, "double", None) {
+            self.double();
+        }
+        if ui.add_button(&mut canvas, &mut layout, &interact, "reverse", None) {
+            self.reverse();
+        }
+        layout.next_row();
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "bars", &mut self.bars, 1.0, 1.0, 16.0);
+
+        Some(canvas.into_surface())
+    }
+}
@@ -0,0 +1,695 @@
+//! Small modules doing arithmetic on audio or control signals. Each of them
+//! reads its inputs with `Data::control`, so both kinds can be connected, and
+//! outputs the kind selected in its window.
+
+use crate::*;
+
+const WIDTH: u32 = 260;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Signal {
+    Audio,
+    Control,
+}
+
+impl Signal {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Audio => "audio",
+            Self::Control => "control",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Self::Audio => Self::Control,
+            Self::Control => Self::Audio,
+        }
+    }
+
+    fn data_type(self) -> DataType {
+        match self {
+            Self::Audio => DataType::Audio,
+            Self::Control => DataType::Control,
+        }
+    }
+
+    fn data(self, value: f32) -> Data {
+        match self {
+            Self::Audio => Data::Audio(value),
+            Self::Control => Data::Control(value),
+        }
+    }
+}
+
+fn new_canvas(height: u32) -> sdl2::render::SurfaceCanvas<'static> {
+    use sdl2::{
+        surface::Surface,
+        pixels::PixelFormatEnum,
+    };
+
+    Surface::new(WIDTH, height, PixelFormatEnum::RGBA32)
+        .unwrap().into_canvas().unwrap()
+}
+
+/// Button switching between audio and control output, on its own row.
+fn signal_button(
+        ui: &UiContext,
+        canvas: &mut sdl2::render::SurfaceCanvas,
+        layout: &mut crate::ui_utils::SimpleLayoutBuilder,
+        interact: &Option<ModuleInteractInfo>,
+        signal: &mut Signal) {
+    ui.add_label(canvas, layout, "output", None);
+    if ui.add_button(canvas, layout, interact, signal.as_str(), Some(7)) {
+        *signal = signal.next();
+    }
+    layout.next_row();
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Constant {
+    signal: Signal,
+    value: f32,
+}
+
+impl Constant {
+    pub fn new() -> Self {
+        Self {
+            signal: Signal::Control,
+            value: 1.0,
+        }
+    }
+}
+
+impl Module for Constant {
+    fn tick(&mut self) -> Option<Data> {
+        Some(self.signal.data(self.value))
+    }
+
+    define_module! {
+        title: "Constant",
+        id: "constant",
+        inputs: [],
+    }
+
+    impl_serialization!();
+
+    fn get_output_type(&self) -> DataType {
+        self.signal.data_type()
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        let mut canvas = new_canvas(60);
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        signal_button(ui, &mut canvas, &mut layout, &interact, &mut self.signal);
+        ui.add_param(&mut canvas, &mut layout, &interact, "value", &mut self.value, 0.01, -10.0, 10.0);
+
+        Some(canvas.into_surface())
+    }
+}
+
+/// Sum of two signals, unlike `Mixer` which averages.
+#[derive(Serialize, Deserialize)]
+pub struct Add {
+    signal: Signal,
+
+    #[serde(skip)]
+    inputs: [f32; 2],
+}
+
+impl Add {
+    pub fn new() -> Self {
+        Self {
+            signal: Signal::Audio,
+            inputs: [0.0; 2],
+        }
+    }
+}
+
+impl Module for Add {
+    fn tick(&mut self) -> Option<Data> {
+        Some(self.signal.data(self.inputs[0] + self.inputs[1]))
+    }
+
+    define_module! {
+        title: "Add",
+        id: "add",
+    }
+
+    impl_serialization!();
+
+    fn get_output_type(&self) -> DataType {
+        self.signal.data_type()
+    }
+
+    fn get_inputs(&self) -> Vec<(DataType, &'static str)> {
+        vec![(self.signal.data_type(), "a"), (self.signal.data_type(), "b")]
+    }
+
+    fn send(&mut self, input: usize, data: Data) {
+        self.inputs[input.min(1)] = data.control();
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        let mut canvas = new_canvas(30);
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        signal_button(ui, &mut canvas, &mut layout, &interact, &mut self.signal);
+
+        Some(canvas.into_surface())
+    }
+}
+
+/// Product of two signals, a VCA with a control signal or a ring modulator
+/// with two audio signals.
+#[derive(Serialize, Deserialize)]
+pub struct Multiply {
+    signal: Signal,
+
+    /// Unconnected inputs stay at 1.0 and leave the other one unchanged.
+    #[serde(skip, default = "unity")]
+    inputs: [f32; 2],
+}
+
+fn unity() -> [f32; 2] {
+    [1.0; 2]
+}
+
+impl Multiply {
+    pub fn new() -> Self {
+        Self {
+            signal: Signal::Audio,
+            inputs: unity(),
+        }
+    }
+}
+
+impl Module for Multiply {
+    fn tick(&mut self) -> Option<Data> {
+        Some(self.signal.data(self.inputs[0] * self.inputs[1]))
+    }
+
+    define_module! {
+        title: "Multiply",
+        id: "multiply",
+    }
+
+    impl_serialization!();
+
+    fn get_output_type(&self) -> DataType {
+        self.signal.data_type()
+    }
+
+    fn get_inputs(&self) -> Vec<(DataType, &'static str)> {
+        vec![(self.signal.data_type(), "a"), (self.signal.data_type(), "b")]
+    }
+
+    fn send(&mut self, input: usize, data: Data) {
+        self.inputs[input.min(1)] = data.control();
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        let mut canvas = new_canvas(30);
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        signal_button(ui, &mut canvas, &mut layout, &interact, &mut self.signal);
+
+        Some(canvas.into_surface())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Invert {
+    signal: Signal,
+
+    #[serde(skip)]
+    input: f32,
+}
+
+impl Invert {
+    pub fn new() -> Self {
+        Self {
+            signal: Signal::Audio,
+            input: 0.0,
+        }
+    }
+}
+
+impl Module for Invert {
+    fn tick(&mut self) -> Option<Data> {
+        Some(self.signal.data(-self.input))
+    }
+
+    define_module! {
+        title: "Invert",
+        id: "invert",
+    }
+
+    impl_serialization!();
+
+    fn get_output_type(&self) -> DataType {
+        self.signal.data_type()
+    }
+
+    fn get_inputs(&self) -> Vec<(DataType, &'static str)> {
+        vec![(self.signal.data_type(), "input")]
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.control();
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        let mut canvas = new_canvas(30);
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        signal_button(ui, &mut canvas, &mut layout, &interact, &mut self.signal);
+
+        Some(canvas.into_surface())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ScaleOffset {
+    signal: Signal,
+    scale: f32,
+    offset: f32,
+
+    #[serde(skip)]
+    input: f32,
+}
+
+impl ScaleOffset {
+    pub fn new() -> Self {
+        Self {
+            signal: Signal::Control,
+            scale: 1.0,
+            offset: 0.0,
+            input: 0.0,
+        }
+    }
+}
+
+impl Module for ScaleOffset {
+    fn tick(&mut self) -> Option<Data> {
+        Some(self.signal.data(self.input * self.scale + self.offset))
+    }
+
+    define_module! {
+        title: "ScaleOffset",
+        id: "scale_offset",
+    }
+
+    impl_serialization!();
+
+    fn get_output_type(&self) -> DataType {
+        self.signal.data_type()
+    }
+
+    fn get_inputs(&self) -> Vec<(DataType, &'static str)> {
+        vec![(self.signal.data_type(), "input")]
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.control();
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        let mut canvas = new_canvas(90);
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        signal_button(ui, &mut canvas, &mut layout, &interact, &mut self.signal);
+        ui.add_param(&mut canvas, &mut layout, &interact, "scale", &mut self.scale, 0.05, -10.0, 10.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "offset", &mut self.offset, 0.05, -10.0, 10.0);
+
+        Some(canvas.into_surface())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MinMax {
+    signal: Signal,
+    /// Output the larger of the inputs instead of the smaller one.
+    max: bool,
+
+    #[serde(skip)]
+    inputs: [f32; 2],
+}
+
+impl MinMax {
+    pub fn new() -> Self {
+        Self {
+            signal: Signal::Audio,
+            max: false,
+            inputs: [0.0; 2],
+        }
+    }
+}
+
+impl Module for MinMax {
+    fn tick(&mut self) -> Option<Data> {
+        let [a, b] = self.inputs;
+        Some(self.signal.data(if self.max { a.max(b) } else { a.min(b) }))
+    }
+
+    define_module! {
+        title: "MinMax",
+        id: "min_max",
+    }
+
+    impl_serialization!();
+
+    fn get_output_type(&self) -> DataType {
+        self.signal.data_type()
+    }
+
+    fn get_inputs(&self) -> Vec<(DataType, &'static str)> {
+        vec![(self.signal.data_type(), "a"), (self.signal.data_type(), "b")]
+    }
+
+    fn send(&mut self, input: usize, data: Data) {
+        self.inputs[input.min(1)] = data.control();
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        let mut canvas = new_canvas(60);
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        signal_button(ui, &mut canvas, &mut layout, &interact, &mut self.signal);
+        ui.add_label(&mut canvas, &mut layout, "mode", None);
+        if ui.add_button(&mut canvas, &mut layout, &interact, if self.max { "max" } else { "min" }, Some(3)) {
+            self.max = !self.max;
+        }
+
+        Some(canvas.into_surface())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Crossfade {
+    signal: Signal,
+    /// 0 is only `a`, 1 only `b`, the control input is added to it.
+    position: f32,
+
+    #[serde(skip)]
+    inputs: [f32; 2],
+    #[serde(skip)]
+    modulation: f32,
+}
+
+impl Crossfade {
+    pub fn new() -> Self {
+        Self {
+            signal: Signal::Audio,
+            position: 0.5,
+            inputs: [0.0; 2],
+            modulation: 0.0,
+        }
+    }
+}
+
+impl Module for Crossfade {
+    fn tick(&mut self) -> Option<Data> {
+        let [a, b] = self.inputs;
+        let position = (self.position + self.modulation).clamp(0.0, 1.0);
+        Some(self.signal.data(a + (b - a) * position))
+    }
+
+    define_module! {
+        title: "Crossfade",
+        id: "crossfade",
+    }
+
+    impl_serialization!();
+
+    fn get_output_type(&self) -> DataType {
+        self.signal.data_type()
+    }
+
+    fn get_inputs(&self) -> Vec<(DataType, &'static str)> {
+        vec![
+            (self.signal.data_type(), "a"),
+            (self.signal.data_type(), "b"),
+            (DataType::Control, "position"),
+        ]
+    }
+
+    fn send(&mut self, input: usize, data: Data) {
+        match input {
+            0 | 1 => self.inputs[input] = data.control(),
+            _ => self.modulation = data.control(),
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        let mut canvas = new_canvas(60);
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        signal_button(ui, &mut canvas, &mut layout, &interact, &mut self.signal);
+        ui.add_param(&mut canvas, &mut layout, &interact, "position", &mut self.position, 0.05, 0.0, 1.0);
+
+        Some(canvas.into_surface())
+    }
+}
+
+/// Holds the input from one incoming note to the next.
+#[derive(Serialize, Deserialize)]
+pub struct SampleHold {
+    signal: Signal,
+
+    #[serde(skip)]
+    input: f32,
+    #[serde(skip)]
+    triggered: bool,
+    #[serde(skip)]
+    held: f32,
+}
+
+impl SampleHold {
+    pub fn new() -> Self {
+        Self {
+            signal: Signal::Control,
+            input: 0.0,
+            triggered: false,
+            held: 0.0,
+        }
+    }
+}
+
+impl Module for SampleHold {
+    fn tick(&mut self) -> Option<Data> {
+        if self.triggered {
+            self.triggered = false;
+            self.held = self.input;
+        }
+        Some(self.signal.data(self.held))
+    }
+
+    define_module! {
+        title: "SampleHold",
+        id: "sample_hold",
+    }
+
+    impl_serialization!();
+
+    fn get_output_type(&self) -> DataType {
+        self.signal.data_type()
+    }
+
+    fn get_inputs(&self) -> Vec<(DataType, &'static str)> {
+        vec![(self.signal.data_type(), "input"), (DataType::Notes, "clock")]
+    }
+
+    fn send(&mut self, input: usize, data: Data) {
+        match input {
+            0 => self.input = data.control(),
+            _ => if !data.notes().is_empty() {
+                self.triggered = true;
+            }
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        let mut canvas = new_canvas(60);
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        signal_button(ui, &mut canvas, &mut layout, &interact, &mut self.signal);
+        ui.add_label(&mut canvas, &mut layout, &format!("held {:.3}", self.held), None);
+
+        Some(canvas.into_surface())
+    }
+}
+
+/// Limits how fast the output can follow the input.
+#[derive(Serialize, Deserialize)]
+pub struct SlewLimiter {
+    signal: Signal,
+    /// Milliseconds to rise and fall by 1.0.
+    rise: f32,
+    fall: f32,
+
+    #[serde(skip)]
+    input: f32,
+    #[serde(skip)]
+    value: f32,
+}
+
+impl SlewLimiter {
+    pub fn new() -> Self {
+        Self {
+            signal: Signal::Control,
+            rise: 100.0,
+            fall: 100.0,
+            input: 0.0,
+            value: 0.0,
+        }
+    }
+}
+
+impl Module for SlewLimiter {
+    fn tick(&mut self) -> Option<Data> {
+        let sample_rate = get_sample_rate() as f32;
+        if sample_rate == 0.0 {
+            return Some(self.signal.data(0.0))
+        }
+
+        let step = |time: f32| 1000.0 / (time.max(0.01) * sample_rate);
+        let change = self.input - self.value;
+        self.value += change.clamp(-step(self.fall), step(self.rise));
+
+        Some(self.signal.data(self.value))
+    }
+
+    define_module! {
+        title: "SlewLimiter",
+        id: "slew_limiter",
+    }
+
+    impl_serialization!();
+
+    fn get_output_type(&self) -> DataType {
+        self.signal.data_type()
+    }
+
+    fn get_inputs(&self) -> Vec<(DataType, &'static str)> {
+        vec![(self.signal.data_type(), "input")]
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.control();
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        let mut canvas = new_canvas(90);
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        signal_button(ui, &mut canvas, &mut layout, &interact, &mut self.signal);
+        ui.add_param(&mut canvas, &mut layout, &interact, "rise ms", &mut self.rise, 5.0, 0.0, 5000.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "fall ms", &mut self.fall, 5.0, 0.0, 5000.0);
+
+        Some(canvas.into_surface())
+    }
+}
+
+/// Outputs 1.0 while the input is above the reference plus the threshold and
+/// 0.0 otherwise, switching only once the input leaves the hysteresis band.
+#[derive(Serialize, Deserialize)]
+pub struct Comparator {
+    signal: Signal,
+    threshold: f32,
+    hysteresis: f32,
+
+    #[serde(skip)]
+    inputs: [f32; 2],
+    #[serde(skip)]
+    high: bool,
+}
+
+impl Comparator {
+    pub fn new() -> Self {
+        Self {
+            signal: Signal::Control,
+            threshold: 0.0,
+            hysteresis: 0.0,
+            inputs: [0.0; 2],
+            high: false,
+        }
+    }
+}
+
+impl Module for Comparator {
+    fn tick(&mut self) -> Option<Data> {
+        let [input, reference] = self.inputs;
+        let level = reference + self.threshold;
+        let band = self.hysteresis / 2.0;
+
+        if level + band < input {
+            self.high = true;
+        } else if input < level - band {
+            self.high = false;
+        }
+
+        Some(self.signal.data(if self.high { 1.0 } else { 0.0 }))
+    }
+
+    define_module! {
+        title: "Comparator",
+        id: "comparator",
+    }
+
+    impl_serialization!();
+
+    fn get_output_type(&self) -> DataType {
+        self.signal.data_type()
+    }
+
+    fn get_inputs(&self) -> Vec<(DataType, &'static str)> {
+        vec![(self.signal.data_type(), "input"), (self.signal.data_type(), "reference")]
+    }
+
+    fn send(&mut self, input: usize, data: Data) {
+        self.inputs[input.min(1)] = data.control();
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        let mut canvas = new_canvas(90);
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        signal_button(ui, &mut canvas, &mut layout, &interact, &mut self.signal);
+        ui.add_param(&mut canvas, &mut layout, &interact, "threshold", &mut self.threshold, 0.05, -10.0, 10.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "hysteresis", &mut self.hysteresis, 0.01, 0.0, 1.0);
+
+        Some(canvas.into_surface())
+    }
+}
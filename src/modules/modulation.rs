@@ -0,0 +1,229 @@
+use crate::*;
+use std::f32::consts::{PI, TAU};
+
+const MAX_DELAY_SECS: f32 = 0.05;
+const PHASER_STAGES: usize = 4;
+
+// tempo synced LFO periods, in beats
+const SYNC_DIVISIONS: [(f32, &str); 6] = [
+    (8.0, "2/1"),
+    (4.0, "1/1"),
+    (2.0, "1/2"),
+    (1.0, "1/4"),
+    (0.5, "1/8"),
+    (0.25, "1/16"),
+];
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum ModulationMode {
+    Chorus,
+    Flanger,
+    Phaser,
+}
+
+/// First order allpass section used by the phaser.
+#[derive(Clone, Copy, Default)]
+struct Allpass {
+    last_input: f32,
+    last_output: f32,
+}
+
+impl Allpass {
+    fn process(&mut self, input: f32, coefficient: f32) -> f32 {
+        let output = coefficient * input + self.last_input - coefficient * self.last_output;
+        self.last_input = input;
+        self.last_output = output;
+        output
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Modulation {
+    mode: ModulationMode,
+    rate: f32,
+    depth: f32,
+    feedback: f32,
+    mix: f32,
+    sync: Option<usize>,
+
+    #[serde(skip)]
+    input: f32,
+    #[serde(skip)]
+    phase: f32,
+    #[serde(skip)]
+    delay: DelayLine,
+    #[serde(skip)]
+    allpasses: [Allpass; PHASER_STAGES],
+    #[serde(skip)]
+    last_output: f32,
+}
+
+impl Modulation {
+    pub fn new() -> Self {
+        Self {
+            mode: ModulationMode::Chorus,
+            rate: 0.5,
+            depth: 0.5,
+            feedback: 0.0,
+            mix: 0.5,
+            sync: None,
+            input: 0.0,
+            phase: 0.0,
+            delay: DelayLine::default(),
+            allpasses: Default::default(),
+            last_output: 0.0,
+        }
+    }
+
+    fn lfo_rate(&self) -> f32 {
+        match self.sync {
+            Some(division) => BPM / 60.0 / SYNC_DIVISIONS[division].0,
+            None => self.rate,
+        }
+    }
+
+    fn process_delay(&mut self, lfo: f32, sample_rate: f32) -> f32 {
+        let ms = match self.mode {
+            ModulationMode::Chorus => 20.0 + lfo * self.depth * 8.0,
+            _ => 2.5 + lfo * self.depth * 2.0,
+        };
+
+        // allocated once the sample rate is known
+        if self.delay.is_empty() {
+            return self.input
+        }
+
+        self.delay.push(self.input + self.last_output * self.feedback);
+        self.delay.read(ms / 1000.0 * sample_rate)
+    }
+
+    fn process_phaser(&mut self, lfo: f32, sample_rate: f32) -> f32 {
+        // sweep the allpass break frequency exponentially between 200 and 3200 Hz
+        let freq = 200.0 * 16.0_f32.powf((lfo * self.depth + 1.0) / 2.0);
+        let tan = (PI * freq / sample_rate).tan();
+        let coefficient = (tan - 1.0) / (tan + 1.0);
+
+        let mut value = self.input + self.last_output * self.feedback;
+        for allpass in self.allpasses.iter_mut() {
+            value = allpass.process(value, coefficient);
+        }
+        value
+    }
+
+    fn cycle_mode(&mut self) {
+        use ModulationMode::*;
+        self.mode = match self.mode {
+            Chorus => Flanger,
+            Flanger => Phaser,
+            Phaser => Chorus,
+        };
+        self.last_output = 0.0;
+    }
+
+    fn cycle_sync(&mut self) {
+        self.sync = match self.sync {
+            None => Some(0),
+            Some(division) if division + 1 < SYNC_DIVISIONS.len() => Some(division + 1),
+            Some(_) => None,
+        };
+    }
+}
+
+impl Module for Modulation {
+    fn tick(&mut self) -> Option<Data> {
+        let sample_rate = get_sample_rate() as f32;
+        if sample_rate == 0.0 {
+            return Some(Data::Audio(self.input))
+        }
+
+        self.phase = (self.phase + self.lfo_rate() / sample_rate) % 1.0;
+        let lfo = (self.phase * TAU).sin();
+
+        let wet = match self.mode {
+            ModulationMode::Phaser => self.process_phaser(lfo, sample_rate),
+            _ => self.process_delay(lfo, sample_rate),
+        };
+        self.last_output = wet;
+
+        Some(Data::Audio(self.input * (1.0 - self.mix) + wet * self.mix))
+    }
+
+    define_module! {
+        title: "Modulation",
+        id: "modulation",
+        output: Audio,
+        inputs: [(Audio, "audio")],
+    }
+
+    impl_serialization!();
+
+    fn loaded(&mut self) {
+        self.sync = self.sync.filter(|division| *division < SYNC_DIVISIONS.len());
+    }
+
+    fn sample_rate_changed(&mut self, sample_rate: u32) {
+        let max_delay = (MAX_DELAY_SECS * sample_rate as f32) as usize;
+        if self.delay.len() != max_delay {
+            self.delay = DelayLine::new(max_delay);
+        }
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.audio();
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (260, 160);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        ui.add_label(&mut canvas, &mut layout, self.mode.as_str(), Some(8));
+        if ui.add_button(&mut canvas, &mut layout, &interact, "cycle mode", None) {
+            self.cycle_mode();
+        }
+        layout.next_row();
+
+        match self.sync {
+            Some(division) => {
+                ui.add_label(&mut canvas, &mut layout, &format!("rate: {}", SYNC_DIVISIONS[division].1), Some(16));
+                layout.next_row();
+            }
+            None => {
+                ui.add_param(&mut canvas, &mut layout, &interact, "rate", &mut self.rate, 0.05, 0.05, 10.0);
+            }
+        }
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "depth", &mut self.depth, 0.1, 0.0, 1.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "feedback", &mut self.feedback, 0.1, -0.9, 0.9);
+        ui.add_param(&mut canvas, &mut layout, &interact, "mix", &mut self.mix, 0.1, 0.0, 1.0);
+
+        ui.add_label(&mut canvas, &mut layout, if self.sync.is_some() { "synced" } else { "free" }, Some(6));
+        if ui.add_button(&mut canvas, &mut layout, &interact, "cycle sync", None) {
+            self.cycle_sync();
+        }
+
+        Some(canvas.into_surface())
+    }
+}
+
+impl ModulationMode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Chorus => "chorus",
+            Self::Flanger => "flanger",
+            Self::Phaser => "phaser",
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use crate::*;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum NoiseColor {
+    White,
+    Pink,
+    Brown,
+}
+
+impl NoiseColor {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::White => "white",
+            Self::Pink => "pink",
+            Self::Brown => "brown",
+        }
+    }
+
+    fn next(self) -> Self {
+        use NoiseColor::*;
+        match self {
+            White => Pink,
+            Pink => Brown,
+            Brown => White,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Noise {
+    color: NoiseColor,
+    level: f32,
+    seed: u32,
+    /// Output a control value that changes on every incoming note instead of
+    /// audio.
+    sample_hold: bool,
+
+    #[serde(skip)]
+    rng: Rng,
+    /// Seed the generator was started with, `None` until the first tick.
+    #[serde(skip)]
+    rng_seed: Option<u32>,
+    /// State of the pink noise filters.
+    #[serde(skip)]
+    pink: [f32; 3],
+    #[serde(skip)]
+    brown: f32,
+    #[serde(skip)]
+    triggered: bool,
+    #[serde(skip)]
+    held: f32,
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Self {
+            color: NoiseColor::White,
+            level: 0.5,
+            seed: 1,
+            sample_hold: false,
+            rng: Rng::default(),
+            rng_seed: None,
+            pink: [0.0; 3],
+            brown: 0.0,
+            triggered: false,
+            held: 0.0,
+        }
+    }
+
+    /// Start the sequence from the seed again.
+    fn restart(&mut self) {
+        self.rng = Rng::new(self.seed);
+        self.rng_seed = Some(self.seed);
+        self.pink = [0.0; 3];
+        self.brown = 0.0;
+        self.held = 0.0;
+    }
+
+    fn next_value(&mut self) -> f32 {
+        let white = self.rng.next_f32();
+        match self.color {
+            NoiseColor::White => white,
+            NoiseColor::Pink => {
+                // Paul Kellet's economy filter, -3 dB per octave within 1/4 dB
+                self.pink[0] = 0.99765 * self.pink[0] + white * 0.0990460;
+                self.pink[1] = 0.96300 * self.pink[1] + white * 0.2965164;
+                self.pink[2] = 0.57000 * self.pink[2] + white * 1.0526913;
+                (self.pink.iter().sum::<f32>() + white * 0.1848) * 0.25
+            }
+            NoiseColor::Brown => {
+                // leaky integrator, so the signal doesn't wander off
+                self.brown = (self.brown + 0.02 * white) / 1.02;
+                self.brown * 3.5
+            }
+        }
+    }
+}
+
+impl Module for Noise {
+    fn tick(&mut self) -> Option<Data> {
+        if self.rng_seed != Some(self.seed) {
+            self.restart();
+        }
+
+        let value = self.next_value();
+        if !self.sample_hold {
+            return Some(Data::Audio(value * self.level))
+        }
+
+        if self.triggered {
+            self.triggered = false;
+            self.held = value;
+        }
+        Some(Data::Control(self.held * self.level))
+    }
+
+    define_module! {
+        title: "Noise",
+        id: "noise",
+        inputs: [(Notes, "clock")],
+    }
+
+    impl_serialization!();
+
+    fn get_output_type(&self) -> DataType {
+        if self.sample_hold {
+            DataType::Control
+        } else {
+            DataType::Audio
+        }
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        if !data.notes().is_empty() {
+            self.triggered = true;
+        }
+    }
+
+    fn execute(&mut self, cmd: String) {
+        let args: Vec<&str> = cmd.split_whitespace().collect();
+        match args[..] {
+            ["seed", seed] => match seed.parse() {
+                Ok(seed) => self.seed = seed,
+                Err(err) => println!("invalid seed: {err}"),
+            }
+            ["restart"] => self.restart(),
+            _ => println!("commands: seed <number>, restart"),
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (260, 110);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        ui.add_label(&mut canvas, &mut layout, self.color.as_str(), Some(6));
+        if ui.add_button(&mut canvas, &mut layout, &interact, "color", None) {
+            self.color = self.color.next();
+        }
+        let mode = if self.sample_hold { "sample & hold" } else { "continuous" };
+        if ui.add_button(&mut canvas, &mut layout, &interact, mode, Some(13)) {
+            self.sample_hold = !self.sample_hold;
+        }
+        layout.next_row();
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "level", &mut self.level, 0.05, 0.0, 1.0);
+
+        let mut seed = self.seed as f32;
+        if ui.add_param(&mut canvas, &mut layout, &interact, "seed", &mut seed, 1.0, 0.0, 9999.0) {
+            self.seed = seed as u32;
+        }
+        if ui.add_button(&mut canvas, &mut layout, &interact, "restart", None) {
+            self.restart();
+        }
+
+        Some(canvas.into_surface())
+    }
+}
@@ -9,41 +9,67 @@ pub enum Waveshape {
     Saw,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "SavedOscillator", into = "SavedOscillator")]
 pub struct Oscillator {
-    pub waveform: Box<[f32]>,
     pub waveshape: Waveshape,
+    /// Frequency in Hz, 0 while no note was received.
+    freq: f32,
+    /// Position in the current period, from 0 to 1.
+    phase: f32,
+    /// Period length and position in samples of a note from an old save,
+    /// turned into `freq` and `phase` once the sample rate is known.
+    restored: Option<(usize, usize)>,
+}
+
+/// Layout of the old wavetable oscillator, which saves still use so older
+/// projects keep loading.
+#[derive(Serialize, Deserialize)]
+struct SavedOscillator {
+    waveform: Box<[f32]>,
+    waveshape: Waveshape,
     index: usize,
 }
 
+impl From<SavedOscillator> for Oscillator {
+    fn from(saved: SavedOscillator) -> Self {
+        let mut oscillator = Self::new(saved.waveshape);
+        // the table held exactly one period of the playing note
+        if !saved.waveform.is_empty() {
+            oscillator.restored = Some((saved.waveform.len(), saved.index));
+        }
+        oscillator
+    }
+}
+
+impl From<Oscillator> for SavedOscillator {
+    fn from(oscillator: Oscillator) -> Self {
+        Self {
+            waveform: Box::default(),
+            waveshape: oscillator.waveshape,
+            index: 0,
+        }
+    }
+}
+
 impl Oscillator {
     pub fn new(waveshape: Waveshape) -> Self {
         Self {
             waveshape,
-            waveform: vec![].into(),
-            index: 0,
+            freq: 0.0,
+            phase: 0.0,
+            restored: None,
         }
     }
 
-    pub fn set_waveform(&mut self, freq: f32) {
-        self.waveform = self.get_waveform(freq);
-        self.index = 0;
+    pub fn set_freq(&mut self, freq: f32) {
+        self.freq = freq;
+        self.phase = 0.0;
+        self.restored = None;
     }
 
-    fn get_waveform(&self, freq: f32) -> Box<[f32]> {
-        let mut waveform = Vec::new();
-        let max = get_sample_rate() as usize / freq as usize;
-        for i in 0..max {
-            let i = i as f32 / max as f32;
-            let value = match self.waveshape {
-                Waveshape::Sine => self.calculate_sine(i),
-                Waveshape::Square => self.calculate_square(i),
-                Waveshape::Saw => self.calculate_saw(i),
-            };
-
-            waveform.push(value);
-        }
-        waveform.into()
+    pub fn is_playing(&self) -> bool {
+        self.freq != 0.0
     }
 
     fn calculate_sine(&self, index: f32) -> f32 {
@@ -61,12 +87,23 @@ impl Oscillator {
 
 impl Module for Oscillator {
     fn tick(&mut self) -> Option<Data> {
-        Some(if !self.waveform.is_empty() {
-            self.index = (self.index + 1) % self.waveform.len();
-            Data::Audio(self.waveform[self.index])
-        } else {
-            Data::Audio(0.0)
-        })
+        let sample_rate = get_sample_rate();
+        if sample_rate != 0 && let Some((len, index)) = self.restored.take() {
+            self.freq = sample_rate as f32 / len as f32;
+            self.phase = (index % len) as f32 / len as f32;
+        }
+        if !self.is_playing() || sample_rate == 0 {
+            return Some(Data::Audio(0.0))
+        }
+
+        // a fractional phase keeps the pitch exact, a table with a whole
+        // number of samples per period rounds the frequency
+        self.phase = (self.phase + self.freq / sample_rate as f32) % 1.0;
+        Some(Data::Audio(match self.waveshape {
+            Waveshape::Sine => self.calculate_sine(self.phase),
+            Waveshape::Square => self.calculate_square(self.phase),
+            Waveshape::Saw => self.calculate_saw(self.phase),
+        }))
     }
 
     define_module! {
@@ -77,7 +114,7 @@ impl Module for Oscillator {
     }
 
     fn send(&mut self, _input: usize, data: Data) {
-        self.set_waveform(data.notes()[0].freq());
+        self.set_freq(data.notes()[0].freq());
     }
 }
 
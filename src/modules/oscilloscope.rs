@@ -0,0 +1,232 @@
+use crate::*;
+
+const HISTORY_SIZE: usize = 1 << 16;
+const DIVISIONS: u32 = 8;
+/// Selectable time bases in milliseconds per division.
+const TIME_BASES: [f32; 9] = [0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0];
+
+const GRAPH_WIDTH: u32 = 400;
+const GRAPH_HEIGHT: u32 = 200;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TriggerEdge {
+    Rising,
+    Falling,
+    /// Don't wait for a trigger, always show the latest samples.
+    Free,
+}
+
+impl TriggerEdge {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Rising => "rising",
+            Self::Falling => "falling",
+            Self::Free => "free",
+        }
+    }
+
+    fn next(self) -> Self {
+        use TriggerEdge::*;
+        match self {
+            Rising => Falling,
+            Falling => Free,
+            Free => Rising,
+        }
+    }
+}
+
+fn scope_buffer() -> RingBuffer {
+    RingBuffer::new(HISTORY_SIZE)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Oscilloscope {
+    time_base: usize,
+    trigger_level: f32,
+    edge: TriggerEdge,
+
+    #[serde(skip)]
+    frozen: bool,
+    #[serde(skip)]
+    input: f32,
+    /// Samples from `tick` waiting for `draw`. Both are called with the App
+    /// lock held, so the buffer is written and read under it and only bounds
+    /// what piles up between two frames.
+    #[serde(skip, default = "scope_buffer")]
+    buffer: RingBuffer,
+    #[serde(skip)]
+    history: Vec<f32>,
+}
+
+impl Oscilloscope {
+    pub fn new() -> Self {
+        Self {
+            time_base: 3,
+            trigger_level: 0.0,
+            edge: TriggerEdge::Rising,
+            frozen: false,
+            input: 0.0,
+            buffer: scope_buffer(),
+            history: Vec::with_capacity(HISTORY_SIZE * 2),
+        }
+    }
+
+    /// Move new samples from the ring buffer into the history, keeping the
+    /// last `HISTORY_SIZE` of them.
+    fn collect(&mut self) {
+        while let Some(sample) = self.buffer.pop() {
+            if !self.frozen {
+                self.history.push(sample);
+            }
+        }
+        if HISTORY_SIZE < self.history.len() {
+            self.history.drain(..self.history.len() - HISTORY_SIZE);
+        }
+    }
+
+    /// The samples to display, starting at the latest trigger that still
+    /// leaves a full screen of samples after it.
+    fn visible(&self, len: usize) -> &[f32] {
+        let Some(last) = self.history.len().checked_sub(len) else {
+            return &self.history
+        };
+
+        let level = self.trigger_level;
+        let start = (1..=last).rev().find(|i| {
+            let (prev, value) = (self.history[i - 1], self.history[*i]);
+            match self.edge {
+                TriggerEdge::Rising => prev < level && level <= value,
+                TriggerEdge::Falling => level < prev && value <= level,
+                TriggerEdge::Free => false,
+            }
+        }).unwrap_or(last);
+
+        &self.history[start..start + len]
+    }
+
+    fn draw_graph(&self, canvas: &mut sdl2::render::SurfaceCanvas) {
+        use sdl2::{
+            pixels::Color,
+            rect::{Point, Rect},
+        };
+
+        canvas.set_draw_color(Color::RGB(230, 230, 230));
+        canvas.fill_rect(Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT)).unwrap();
+
+        canvas.set_draw_color(Color::RGB(190, 190, 190));
+        for i in 1..DIVISIONS {
+            let x = (i * GRAPH_WIDTH / DIVISIONS) as i32;
+            canvas.draw_line((x, 0), (x, GRAPH_HEIGHT as i32)).unwrap();
+        }
+        for i in 1..4 {
+            let y = (i * GRAPH_HEIGHT / 4) as i32;
+            canvas.draw_line((0, y), (GRAPH_WIDTH as i32, y)).unwrap();
+        }
+
+        let half = GRAPH_HEIGHT as f32 / 2.0;
+        let to_y = |value: f32| (half - value.clamp(-1.0, 1.0) * half) as i32;
+
+        if self.edge != TriggerEdge::Free {
+            let y = to_y(self.trigger_level);
+            canvas.set_draw_color(Color::RGB(200, 0, 0));
+            for x in (0..GRAPH_WIDTH as i32).step_by(8) {
+                canvas.draw_line((x, y), (x + 3, y)).unwrap();
+            }
+        }
+
+        let ms = TIME_BASES[self.time_base] * DIVISIONS as f32;
+        let len = (ms / 1000.0 * get_sample_rate() as f32).max(2.0) as usize;
+        let samples = self.visible(len);
+
+        canvas.set_draw_color(Color::RGB(0, 0, 200));
+        if samples.len() < GRAPH_WIDTH as usize {
+            // few samples, connect them instead of drawing min/max columns
+            let points: Vec<Point> = samples.iter().enumerate()
+                .map(|(i, value)| Point::new(
+                    (i * GRAPH_WIDTH as usize / len.max(1)) as i32,
+                    to_y(*value)))
+                .collect();
+            canvas.draw_lines(&points[..]).unwrap();
+        } else {
+            let rect = Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT);
+            crate::ui_utils::draw_waveform(canvas, rect, samples);
+        }
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_rect(Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT)).unwrap();
+    }
+}
+
+impl Module for Oscilloscope {
+    fn tick(&mut self) -> Option<Data> {
+        // dropping samples while the window isn't drawn is fine
+        self.buffer.push(self.input);
+
+        // pass the input through so the scope can be put inline
+        Some(Data::Audio(self.input))
+    }
+
+    define_module! {
+        title: "Oscilloscope",
+        id: "oscilloscope",
+        output: Audio,
+        inputs: [(Audio, "audio")],
+    }
+
+    impl_serialization!();
+
+    fn loaded(&mut self) {
+        self.time_base = self.time_base.min(TIME_BASES.len() - 1);
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.audio();
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (GRAPH_WIDTH, GRAPH_HEIGHT + 90);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        self.collect();
+        self.draw_graph(&mut canvas);
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new(
+            (0, GRAPH_HEIGHT as i32 + 5), mouse_pos);
+
+        if ui.add_button(&mut canvas, &mut layout, &interact, "<", None) {
+            self.time_base = self.time_base.saturating_sub(1);
+        }
+        if ui.add_button(&mut canvas, &mut layout, &interact, ">", None) {
+            self.time_base = (self.time_base + 1).min(TIME_BASES.len() - 1);
+        }
+        let time_base = format!("{} ms/div", TIME_BASES[self.time_base]);
+        ui.add_label(&mut canvas, &mut layout, &time_base, Some(12));
+
+        let freeze = if self.frozen { "frozen" } else { "freeze" };
+        if ui.add_button(&mut canvas, &mut layout, &interact, freeze, None) {
+            self.frozen = !self.frozen;
+        }
+        layout.next_row();
+
+        ui.add_label(&mut canvas, &mut layout, self.edge.as_str(), Some(10));
+        if ui.add_button(&mut canvas, &mut layout, &interact, "edge", None) {
+            self.edge = self.edge.next();
+        }
+        layout.next_row();
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "trigger", &mut self.trigger_level, 0.05, -1.0, 1.0);
+
+        Some(canvas.into_surface())
+    }
+}
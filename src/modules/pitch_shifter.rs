@@ -0,0 +1,121 @@
+use crate::*;
+use std::f32::consts::PI;
+
+/// Longest window in milliseconds the delay line is allocated for.
+const MAX_WINDOW: f32 = 200.0;
+
+/// Delay line pitch shifter: two read taps sweep through a short window at
+/// the shifted rate and are crossfaded so each one is silent when it jumps
+/// back. The length of the signal doesn't change, only its pitch.
+#[derive(Serialize, Deserialize)]
+pub struct PitchShifter {
+    /// Shift in semitones.
+    semitones: f32,
+    cents: f32,
+    /// Length of the sweep in milliseconds, short windows suit percussive
+    /// material while long ones warble less on sustained notes.
+    window: f32,
+    mix: f32,
+
+    #[serde(skip)]
+    line: DelayLine,
+    #[serde(skip)]
+    line_rate: u32,
+    #[serde(skip)]
+    phase: f32,
+    #[serde(skip)]
+    input: f32,
+}
+
+impl PitchShifter {
+    pub fn new() -> Self {
+        Self {
+            semitones: 0.0,
+            cents: 0.0,
+            window: 60.0,
+            mix: 1.0,
+            line: DelayLine::default(),
+            line_rate: 0,
+            phase: 0.0,
+            input: 0.0,
+        }
+    }
+
+    fn ratio(&self) -> f32 {
+        2.0_f32.powf((self.semitones + self.cents / 100.0) / 12.0)
+    }
+}
+
+impl Module for PitchShifter {
+    fn tick(&mut self) -> Option<Data> {
+        // allocated once the sample rate is known
+        if self.line.is_empty() {
+            return Some(Data::Audio(0.0))
+        }
+        let sample_rate = self.line_rate;
+
+        self.line.push(self.input);
+
+        let window = self.window / 1000.0 * sample_rate as f32;
+        // the delay shrinks by the amount the taps read ahead of the input
+        self.phase = (self.phase + (1.0 - self.ratio()) / window).rem_euclid(1.0);
+
+        let mut shifted = 0.0;
+        for phase in [self.phase, (self.phase + 0.5) % 1.0] {
+            let gain = (PI * phase).sin().powi(2);
+            shifted += self.line.read(phase * window) * gain;
+        }
+
+        Some(Data::Audio(self.input + (shifted - self.input) * self.mix))
+    }
+
+    define_module! {
+        title: "PitchShifter",
+        id: "pitch_shifter",
+        output: Audio,
+        inputs: [(Audio, "input")],
+    }
+
+    impl_serialization!();
+
+    fn loaded(&mut self) {
+        // the delay line only holds the longest window
+        self.window = self.window.clamp(20.0, MAX_WINDOW);
+    }
+
+    fn sample_rate_changed(&mut self, sample_rate: u32) {
+        if self.line_rate != sample_rate {
+            self.line = DelayLine::new((MAX_WINDOW / 1000.0 * sample_rate as f32) as usize + 2);
+            self.line_rate = sample_rate;
+        }
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.audio();
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (260, 110);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "semitones", &mut self.semitones, 1.0, -24.0, 24.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "cents", &mut self.cents, 1.0, -100.0, 100.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "window ms", &mut self.window, 5.0, 20.0, MAX_WINDOW);
+        ui.add_param(&mut canvas, &mut layout, &interact, "mix", &mut self.mix, 0.05, 0.0, 1.0);
+
+        Some(canvas.into_surface())
+    }
+}
@@ -0,0 +1,215 @@
+use crate::*;
+
+/// The input is decimated by this factor before pitch detection, which
+/// keeps YIN cheap enough to run in the audio callback.
+const DECIMATION: usize = 2;
+const WINDOW_SIZE: usize = 1024;
+const HOP_SIZE: usize = 256;
+const YIN_THRESHOLD: f32 = 0.15;
+/// Hops a new pitch has to be stable for before it counts as a new note
+/// without an onset.
+const STABLE_HOPS: u32 = 2;
+
+/// Onset detection and pitch estimation on a mono signal, producing the
+/// notes to play. Shared by the module and the offline `analyze` command.
+struct Tracker {
+    sample_rate: f32,
+    filter: Biquad,
+    decimation: usize,
+    window: Vec<f32>,
+    difference: Vec<f32>,
+    hop: usize,
+    level: f32,
+    note: Option<u8>,
+    candidate: Option<(u8, u32)>,
+}
+
+impl Tracker {
+    fn new(sample_rate: u32) -> Self {
+        let sample_rate = sample_rate as f32;
+        Self {
+            sample_rate,
+            // anti-alias filter for the decimation
+            filter: Biquad::lowpass(sample_rate / DECIMATION as f32 * 0.4, 0.7, sample_rate),
+            decimation: 0,
+            window: vec![0.0; WINDOW_SIZE],
+            difference: Vec::with_capacity(WINDOW_SIZE / 2),
+            hop: 0,
+            level: f32::MIN,
+            note: None,
+            candidate: None,
+        }
+    }
+
+    /// Feed one sample, returning the notes to play when they change. An
+    /// empty list releases the current note.
+    fn process(&mut self, sample: f32, gate: f32, onset: f32) -> Option<Box<[Note]>> {
+        let sample = self.filter.process(sample);
+        self.decimation = (self.decimation + 1) % DECIMATION;
+        if self.decimation != 0 {
+            return None
+        }
+
+        self.window[WINDOW_SIZE - HOP_SIZE + self.hop] = sample;
+        self.hop += 1;
+        if self.hop < HOP_SIZE {
+            return None
+        }
+        self.hop = 0;
+
+        let result = self.analyze(gate, onset);
+        self.window.copy_within(HOP_SIZE.., 0);
+        result
+    }
+
+    fn analyze(&mut self, gate: f32, onset: f32) -> Option<Box<[Note]>> {
+        let hop = &self.window[WINDOW_SIZE - HOP_SIZE..];
+        let level = amplitude_to_db(
+            (hop.iter().map(|value| value * value).sum::<f32>() / HOP_SIZE as f32).sqrt());
+        let attack = onset < level - self.level;
+        self.level = level;
+
+        if level < gate {
+            self.candidate = None;
+            return self.note.take().map(|_| Box::new([]) as Box<[Note]>)
+        }
+
+        let freq = yin(&self.window, &mut self.difference, self.sample_rate / DECIMATION as f32, YIN_THRESHOLD)?;
+        let note = freq_to_midi(freq).round().clamp(0.0, 127.0) as u8;
+
+        let stable = match &mut self.candidate {
+            Some((candidate, hops)) if *candidate == note => {
+                *hops += 1;
+                STABLE_HOPS <= *hops
+            }
+            _ => {
+                self.candidate = Some((note, 1));
+                false
+            }
+        };
+
+        // retrigger on onsets, follow legato pitch changes once stable
+        if attack || (stable && self.note != Some(note)) || self.note.is_none() {
+            self.note = Some(note);
+            Some(Box::new([Note::Midi(note)]))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PitchTracker {
+    /// Level in dB below which the note is released.
+    gate: f32,
+    /// Rise in level in dB between hops that counts as a new note.
+    onset: f32,
+
+    #[serde(skip)]
+    input: f32,
+    #[serde(skip)]
+    tracker: Option<Tracker>,
+}
+
+impl PitchTracker {
+    pub fn new() -> Self {
+        Self {
+            gate: -40.0,
+            onset: 6.0,
+            input: 0.0,
+            tracker: None,
+        }
+    }
+
+    /// Run the tracker over a WAV file, listing the notes it finds. Slow,
+    /// so it is run on its own thread.
+    fn analyze(path: &str, gate: f32, onset: f32) -> anyhow::Result<String> {
+        let buffer = AudioBuffer::load_wav(path)?;
+        let mut tracker = Tracker::new(buffer.sample_rate);
+        let mut result = String::new();
+
+        for (i, sample) in buffer.samples.iter().enumerate() {
+            if let Some(notes) = tracker.process(*sample, gate, onset) {
+                let time = i as f32 / buffer.sample_rate as f32;
+                let line = match notes.first() {
+                    Some(note) => format!("{time:8.3}s  {} ({:.1} Hz)\n",
+                        note_name(freq_to_midi(note.freq()).round() as u8), note.freq()),
+                    None => format!("{time:8.3}s  off\n"),
+                };
+                result.push_str(&line);
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl Module for PitchTracker {
+    fn tick(&mut self) -> Option<Data> {
+        let (gate, onset) = (self.gate, self.onset);
+        self.tracker.as_mut()?
+            .process(self.input, gate, onset)
+            .map(Data::Notes)
+    }
+
+    define_module! {
+        title: "PitchTracker",
+        id: "pitch_tracker",
+        output: Notes,
+        inputs: [(Audio, "audio")],
+    }
+
+    impl_serialization!();
+
+    fn sample_rate_changed(&mut self, sample_rate: u32) {
+        if self.tracker.as_ref().is_none_or(|tracker| tracker.sample_rate != sample_rate as f32) {
+            self.tracker = Some(Tracker::new(sample_rate));
+        }
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.audio();
+    }
+
+    fn execute(&mut self, cmd: String) {
+        let args: Vec<&str> = cmd.split_whitespace().collect();
+        match args[..] {
+            ["analyze", path] => {
+                let path = path.to_string();
+                let (gate, onset) = (self.gate, self.onset);
+                std::thread::spawn(move || match Self::analyze(&path, gate, onset) {
+                    Ok(result) => print!("{result}"),
+                    Err(err) => println!("analyzing {path} failed: {err}"),
+                });
+            }
+            _ => println!("commands: analyze <file.wav>"),
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (260, 80);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        let note = self.tracker.as_ref().and_then(|tracker| tracker.note);
+        let text = note.map_or("-".to_string(), note_name);
+        ui.add_label(&mut canvas, &mut layout, &text, None);
+        layout.next_row();
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "gate dB", &mut self.gate, 1.0, -80.0, 0.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "onset dB", &mut self.onset, 1.0, 1.0, 24.0);
+
+        Some(canvas.into_surface())
+    }
+}
@@ -0,0 +1,192 @@
+use crate::*;
+
+const MAX_VOICES: usize = 8;
+/// Lowest note the delay lines are long enough for.
+const MIN_FREQ: f32 = 20.0;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Excitation {
+    Noise,
+    /// A string pulled aside at one point and released.
+    Triangle,
+    Impulse,
+}
+
+impl Excitation {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Noise => "noise",
+            Self::Triangle => "triangle",
+            Self::Impulse => "impulse",
+        }
+    }
+
+    fn next(self) -> Self {
+        use Excitation::*;
+        match self {
+            Noise => Triangle,
+            Triangle => Impulse,
+            Impulse => Noise,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct Voice {
+    line: DelayLine,
+    /// Loop length in samples, minus the delay of the loop filter.
+    period: f32,
+    /// Gain per round trip for the decay time.
+    gain: f32,
+    lowpass: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Pluck {
+    excitation: Excitation,
+    /// Cutoff of the excitation, from dull to bright.
+    brightness: f32,
+    /// Loss of high frequencies in each round trip.
+    damping: f32,
+    /// Time in seconds to decay by 60 dB.
+    decay: f32,
+    level: f32,
+
+    #[serde(skip)]
+    voices: Vec<Voice>,
+    #[serde(skip)]
+    next_voice: usize,
+    #[serde(skip)]
+    voice_rate: u32,
+    #[serde(skip)]
+    rng: Rng,
+}
+
+impl Pluck {
+    pub fn new() -> Self {
+        Self {
+            excitation: Excitation::Noise,
+            brightness: 0.7,
+            damping: 0.3,
+            decay: 3.0,
+            level: 0.5,
+            voices: Vec::new(),
+            next_voice: 0,
+            voice_rate: 0,
+            rng: Rng::default(),
+        }
+    }
+
+    /// Coefficient of the one pole lowpass in the loop.
+    fn loop_coefficient(&self) -> f32 {
+        1.0 - self.damping * 0.9
+    }
+
+    fn pluck(&mut self, freq: f32, velocity: u8) {
+        let sample_rate = self.voice_rate as f32;
+        let freq = freq.clamp(MIN_FREQ, sample_rate / 4.0);
+        let length = (sample_rate / freq).round() as usize;
+
+        // phase delay of the one pole lowpass at the played frequency
+        let feedback = 1.0 - self.loop_coefficient();
+        let w = std::f32::consts::TAU * freq / sample_rate;
+        let filter_delay = (feedback * w.sin()).atan2(1.0 - feedback * w.cos()) / w;
+
+        let amplitude = velocity as f32 / 127.0;
+        let brightness = self.brightness * self.brightness;
+        let (excitation, rng) = (self.excitation, &mut self.rng);
+
+        let voice = &mut self.voices[self.next_voice];
+        self.next_voice = (self.next_voice + 1) % MAX_VOICES;
+
+        voice.period = (sample_rate / freq - filter_delay).max(1.0);
+        voice.gain = 10.0_f32.powf(-3.0 / (self.decay.max(0.01) * freq));
+        voice.lowpass = 0.0;
+
+        // fill one period with the excitation, smoothed by the brightness
+        let mut smoothed = 0.0;
+        for i in 0..length {
+            let position = i as f32 / length as f32;
+            let value = match excitation {
+                Excitation::Noise => rng.next_f32(),
+                Excitation::Triangle => 1.0 - (position * 2.0 - 1.0).abs() * 2.0,
+                Excitation::Impulse => if i == 0 { 1.0 } else { 0.0 },
+            };
+            smoothed += (value - smoothed) * brightness.max(0.01);
+            voice.line.push(smoothed * amplitude);
+        }
+    }
+}
+
+impl Module for Pluck {
+    fn tick(&mut self) -> Option<Data> {
+        let coefficient = self.loop_coefficient();
+        let mut value = 0.0;
+        for voice in self.voices.iter_mut() {
+            let delayed = voice.line.read(voice.period - 1.0);
+            voice.lowpass += (delayed - voice.lowpass) * coefficient;
+            let output = voice.lowpass * voice.gain;
+            voice.line.push(output);
+            value += output;
+        }
+
+        Some(Data::Audio(value * self.level))
+    }
+
+    define_module! {
+        title: "Pluck",
+        id: "pluck",
+        output: Audio,
+        inputs: [(Notes, "notes")],
+    }
+
+    impl_serialization!();
+
+    fn sample_rate_changed(&mut self, sample_rate: u32) {
+        if self.voice_rate != sample_rate {
+            let length = (sample_rate as f32 / MIN_FREQ) as usize + 2;
+            self.voices = vec![Voice { line: DelayLine::new(length), ..Default::default() }; MAX_VOICES];
+            self.voice_rate = sample_rate;
+        }
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        if self.voice_rate == 0 {
+            return
+        }
+        for note in data.notes() {
+            self.pluck(note.freq(), note.velocity());
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (260, 140);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        ui.add_label(&mut canvas, &mut layout, self.excitation.as_str(), Some(8));
+        if ui.add_button(&mut canvas, &mut layout, &interact, "excitation", None) {
+            self.excitation = self.excitation.next();
+        }
+        layout.next_row();
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "brightness", &mut self.brightness, 0.05, 0.05, 1.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "damping", &mut self.damping, 0.05, 0.0, 1.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "decay s", &mut self.decay, 0.1, 0.1, 20.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "level", &mut self.level, 0.05, 0.0, 1.0);
+
+        Some(canvas.into_surface())
+    }
+}
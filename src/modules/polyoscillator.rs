@@ -26,7 +26,7 @@ impl PolyOscillator {
     pub fn set_freqs(&mut self, freqs: Vec<f32>) {
         for (i, oscillator) in self.oscillators.iter_mut().enumerate() {
             if i < freqs.len() {
-                oscillator.0.set_waveform(freqs[i]);
+                oscillator.0.set_freq(freqs[i]);
                 oscillator.1 = true;
             } else {
                 oscillator.1 = false;
@@ -61,7 +61,7 @@ impl Module for PolyOscillator {
         let mut value = 0.0;
         let mut active = 0;
         for osc in self.oscillators.iter_mut() {
-            if !osc.0.waveform.is_empty() && osc.1 {
+            if osc.0.is_playing() && osc.1 {
                 value += osc.0.tick().unwrap().audio();
                 active += 1;
             }
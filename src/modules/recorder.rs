@@ -0,0 +1,234 @@
+use crate::*;
+
+use std::{
+    sync::{Arc, atomic::{AtomicBool, AtomicU32, Ordering}},
+    thread::JoinHandle,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// about five seconds of slack for the writer thread at 48 kHz
+const BUFFER_SIZE: usize = 1 << 18;
+const WRITE_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Clone, Copy, PartialEq)]
+enum RecorderState {
+    Idle,
+    /// Waiting for the next bar to start recording.
+    Armed,
+    Recording,
+}
+
+/// State shared between the audio callback and the writer thread.
+struct Session {
+    buffer: RingBuffer,
+    stop: AtomicBool,
+    dropped: AtomicU32,
+    file: String,
+}
+
+pub struct Recorder {
+    input: f32,
+    state: RecorderState,
+    session: Option<Arc<Session>>,
+    /// Writer threads that may still be finishing their file.
+    writers: Vec<JoinHandle<()>>,
+    recorded: u64,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self {
+            input: 0.0,
+            state: RecorderState::Idle,
+            session: None,
+            writers: Vec::new(),
+            recorded: 0,
+        }
+    }
+
+    fn file_name() -> String {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        format!("{}-{timestamp}.wav", get_project_name())
+    }
+
+    /// Create the file and start the writer thread. Called from the UI and
+    /// console, never from the audio callback.
+    fn start(&mut self, state: RecorderState) {
+        if self.state != RecorderState::Idle {
+            return
+        }
+
+        let file = Self::file_name();
+        let mut writer = match WavWriter::create(&file, get_sample_rate()) {
+            Ok(writer) => writer,
+            Err(err) => {
+                println!("creating {file} failed: {err}");
+                return
+            }
+        };
+
+        let session = Arc::new(Session {
+            buffer: RingBuffer::new(BUFFER_SIZE),
+            stop: AtomicBool::new(false),
+            dropped: AtomicU32::new(0),
+            file,
+        });
+
+        let thread_session = Arc::clone(&session);
+        self.writers.retain(|writer| !writer.is_finished());
+        self.writers.push(std::thread::spawn(move || {
+            let session = thread_session;
+            let mut chunk = Vec::with_capacity(BUFFER_SIZE);
+
+            loop {
+                // check the flag before draining, so nothing pushed before
+                // stopping is lost
+                let stopping = session.stop.load(Ordering::Acquire);
+
+                chunk.clear();
+                while let Some(sample) = session.buffer.pop() {
+                    chunk.push(sample);
+                }
+                if let Err(err) = writer.write_samples(&chunk) {
+                    eprintln!("writing {} failed: {err}", session.file);
+                    return
+                }
+
+                if stopping {
+                    break
+                }
+                std::thread::sleep(WRITE_INTERVAL);
+            }
+
+            let frames = writer.frames();
+            match writer.finalize() {
+                Ok(()) => println!("recorded {frames} samples to {}", session.file),
+                Err(err) => eprintln!("finalizing {} failed: {err}", session.file),
+            }
+        }));
+
+        self.session = Some(session);
+        self.recorded = 0;
+        self.state = state;
+    }
+
+    fn record(&mut self) {
+        match self.state {
+            RecorderState::Idle => self.start(RecorderState::Recording),
+            // punch in right away
+            RecorderState::Armed => self.state = RecorderState::Recording,
+            RecorderState::Recording => {}
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(session) = self.session.take() {
+            session.stop.store(true, Ordering::Release);
+            let dropped = session.dropped.load(Ordering::Relaxed);
+            if dropped != 0 {
+                println!("{dropped} samples could not be written in time");
+            }
+        }
+        self.state = RecorderState::Idle;
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Module for Recorder {
+    fn tick(&mut self) -> Option<Data> {
+        if self.state == RecorderState::Armed
+                && get_transport_position().is_multiple_of(samples_per_bar().max(1)) {
+            self.state = RecorderState::Recording;
+        }
+
+        if self.state == RecorderState::Recording
+                && let Some(session) = &self.session {
+            if !session.buffer.push(self.input) {
+                session.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            self.recorded += 1;
+        }
+
+        // pass the input through so the recorder can be put inline
+        Some(Data::Audio(self.input))
+    }
+
+    define_module! {
+        title: "Recorder",
+        id: "recorder",
+        output: Audio,
+        inputs: [(Audio, "audio")],
+    }
+
+    /// Wait for the files to be written, as the writer threads would
+    /// otherwise be killed with the header still missing its sizes.
+    fn shutdown(&mut self) {
+        self.stop();
+        for writer in self.writers.drain(..) {
+            let _ = writer.join();
+        }
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.audio();
+    }
+
+    fn execute(&mut self, cmd: String) {
+        match cmd.trim() {
+            "arm" => self.start(RecorderState::Armed),
+            "record" => self.record(),
+            "stop" => self.stop(),
+            _ => println!("commands: arm, record, stop"),
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (300, 80);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        if ui.add_button(&mut canvas, &mut layout, &interact, "arm", None) {
+            self.start(RecorderState::Armed);
+        }
+        if ui.add_button(&mut canvas, &mut layout, &interact, "record", None) {
+            self.record();
+        }
+        if ui.add_button(&mut canvas, &mut layout, &interact, "stop", None) {
+            self.stop();
+        }
+        layout.next_row();
+
+        let seconds = self.recorded as f32 / get_sample_rate().max(1) as f32;
+        let state = match self.state {
+            RecorderState::Idle => "idle".to_string(),
+            RecorderState::Armed => "armed, waiting for next bar".to_string(),
+            RecorderState::Recording => format!("recording {seconds:.1}s"),
+        };
+        ui.add_label(&mut canvas, &mut layout, &state, None);
+        layout.next_row();
+
+        if let Some(session) = &self.session {
+            ui.add_label(&mut canvas, &mut layout, &session.file, None);
+        }
+
+        Some(canvas.into_surface())
+    }
+}
@@ -0,0 +1,261 @@
+use crate::*;
+
+const MAX_VOICES: usize = 16;
+const WAVEFORM_HEIGHT: u32 = 100;
+
+struct Voice {
+    position: f64,
+    step: f64,
+    looping: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Sampler {
+    path: Option<String>,
+    root: u8,
+    start: f32,
+    end: f32,
+    loop_enabled: bool,
+    loop_start: f32,
+    loop_end: f32,
+
+    // the file as loaded and resampled to the engine sample rate
+    #[serde(skip)]
+    source: AudioBuffer,
+    #[serde(skip)]
+    sample: AudioBuffer,
+    #[serde(skip)]
+    voices: Vec<Voice>,
+    #[serde(skip)]
+    loader: Loader<(String, anyhow::Result<(AudioBuffer, AudioBuffer)>)>,
+}
+
+impl Sampler {
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            root: 60,
+            start: 0.0,
+            end: 1.0,
+            loop_enabled: false,
+            loop_start: 0.0,
+            loop_end: 1.0,
+            source: AudioBuffer::default(),
+            sample: AudioBuffer::default(),
+            voices: Vec::new(),
+            loader: Loader::default(),
+        }
+    }
+
+    pub fn load(&mut self, path: &str) -> anyhow::Result<()> {
+        let sample_rate = get_sample_rate();
+        self.set_sample(path.to_string(), AudioBuffer::load_wav_resampled(path, sample_rate)?);
+        Ok(())
+    }
+
+    /// Decode `path` on its own thread, the sample is swapped in by `poll`.
+    fn start_load(&mut self, path: &str) {
+        let path = path.to_string();
+        let sample_rate = get_sample_rate();
+        self.loader.start(move || {
+            let result = AudioBuffer::load_wav_resampled(&path, sample_rate);
+            (path, result)
+        });
+    }
+
+    fn poll(&mut self) {
+        if let Some((path, result)) = self.loader.poll() {
+            match result {
+                Ok(buffers) => {
+                    self.set_sample(path.clone(), buffers);
+                    println!("loaded {path} ({} samples)", self.source.len());
+                }
+                Err(err) => println!("loading {path} failed: {err}"),
+            }
+        }
+    }
+
+    fn set_sample(&mut self, path: String, (source, sample): (AudioBuffer, AudioBuffer)) {
+        self.source = source;
+        self.sample = sample;
+        self.voices.clear();
+        self.path = Some(path);
+        self.resample(get_sample_rate());
+    }
+
+    fn resample(&mut self, sample_rate: u32) {
+        if sample_rate != 0 && self.sample.sample_rate != sample_rate && !self.source.is_empty() {
+            self.sample = self.source.resampled(sample_rate);
+        }
+    }
+
+    /// Sample indices for a position given as a fraction of the length.
+    fn index(&self, fraction: f32) -> f64 {
+        fraction as f64 * self.sample.len() as f64
+    }
+
+    fn trigger(&mut self, note: Note) {
+        let step = note.freq() as f64 / midi_to_freq(self.root) as f64;
+        if MAX_VOICES <= self.voices.len() {
+            self.voices.remove(0);
+        }
+        self.voices.push(Voice {
+            position: self.index(self.start),
+            step,
+            looping: self.loop_enabled,
+        });
+    }
+}
+
+impl Module for Sampler {
+    fn tick(&mut self) -> Option<Data> {
+        if self.sample.is_empty() {
+            return Some(Data::Audio(0.0))
+        }
+
+        let end = self.index(self.end);
+        let loop_start = self.index(self.loop_start.max(self.start));
+        let loop_end = self.index(self.loop_end.min(self.end));
+
+        let mut value = 0.0;
+        for voice in self.voices.iter_mut() {
+            value += self.sample.read(voice.position);
+            voice.position += voice.step;
+            if voice.looping && loop_start < loop_end && loop_end <= voice.position {
+                voice.position -= loop_end - loop_start;
+            }
+        }
+        self.voices.retain(|voice| voice.position < end);
+
+        Some(Data::Audio(value))
+    }
+
+    define_module! {
+        title: "Sampler",
+        id: "sampler",
+        output: Audio,
+        inputs: [(Notes, "notes")],
+    }
+
+    impl_serialization!();
+
+    fn loaded(&mut self) {
+        if let Some(path) = self.path.clone()
+                && let Err(err) = self.load(&path) {
+            eprintln!("loading sample '{path}' failed: {err}");
+        }
+    }
+
+    fn sample_rate_changed(&mut self, sample_rate: u32) {
+        self.resample(sample_rate);
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        // held loops are released by the next step, one shots play to the end
+        self.voices.retain(|voice| !voice.looping);
+        for note in data.notes() {
+            self.trigger(note);
+        }
+    }
+
+    fn execute(&mut self, cmd: String) {
+        let mut args = cmd.split_whitespace();
+        match (args.next(), args.next()) {
+            (Some("load"), Some(path)) => self.start_load(path),
+            (Some("root"), Some(note)) => match note.parse() {
+                Ok(note) => self.root = note,
+                Err(err) => println!("invalid note: {err}"),
+            }
+            _ => println!("commands: load <file.wav>, root <midi note>"),
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::{Color, PixelFormatEnum},
+            mouse::MouseButton,
+            rect::Rect,
+        };
+
+        self.poll();
+
+        let (width, height) = (400, WAVEFORM_HEIGHT + 170);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        // left click sets the start point, right click the end point
+        if let Some(info) = &interact
+                && (info.y as u32) < WAVEFORM_HEIGHT {
+            let fraction = info.x as f32 / width as f32;
+            match info.click {
+                Some(MouseButton::Left) => self.start = fraction.min(self.end),
+                Some(MouseButton::Right) => self.end = fraction.max(self.start),
+                _ => {}
+            }
+        }
+
+        let x_of = |fraction: f32| (fraction * width as f32) as i32;
+
+        canvas.set_draw_color(Color::RGB(230, 230, 230));
+        canvas.fill_rect(Rect::new(0, 0, width, WAVEFORM_HEIGHT)).unwrap();
+
+        if self.loop_enabled {
+            canvas.set_draw_color(Color::RGB(200, 220, 200));
+            let (start, end) = (x_of(self.loop_start), x_of(self.loop_end));
+            canvas.fill_rect(Rect::new(start, 0, (end - start).max(1) as u32, WAVEFORM_HEIGHT)).unwrap();
+        }
+
+        canvas.set_draw_color(Color::RGB(0, 0, 200));
+        crate::ui_utils::draw_waveform(&mut canvas, Rect::new(0, 0, width, WAVEFORM_HEIGHT), &self.source.samples);
+
+        canvas.set_draw_color(Color::RGB(200, 0, 0));
+        for fraction in [self.start, self.end] {
+            let x = x_of(fraction).min(width as i32 - 1);
+            canvas.draw_line((x, 0), (x, WAVEFORM_HEIGHT as i32)).unwrap();
+        }
+
+        canvas.set_draw_color(Color::BLACK);
+        if !self.sample.is_empty() {
+            for voice in self.voices.iter() {
+                let x = (voice.position / self.sample.len() as f64 * width as f64) as i32;
+                canvas.draw_line((x, 0), (x, WAVEFORM_HEIGHT as i32)).unwrap();
+            }
+        }
+        canvas.draw_rect(Rect::new(0, 0, width, WAVEFORM_HEIGHT)).unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, WAVEFORM_HEIGHT as i32 + 5), mouse_pos);
+
+        let name = match &self.path {
+            _ if self.loader.is_loading() => "loading...",
+            Some(path) => path.rsplit('/').next().unwrap_or(path),
+            None => "no sample loaded",
+        };
+        ui.add_label(&mut canvas, &mut layout, name, None);
+        layout.next_row();
+
+        let mut root = self.root as f32;
+        if ui.add_param(&mut canvas, &mut layout, &interact, "root", &mut root, 1.0, 0.0, 127.0) {
+            self.root = root as u8;
+        }
+
+        ui.add_label(&mut canvas, &mut layout, if self.loop_enabled { "loop on" } else { "loop off" }, Some(8));
+        if ui.add_button(&mut canvas, &mut layout, &interact, "toggle loop", None) {
+            self.loop_enabled = !self.loop_enabled;
+        }
+        layout.next_row();
+
+        if self.loop_enabled {
+            ui.add_param(&mut canvas, &mut layout, &interact, "loop start", &mut self.loop_start, 0.01, 0.0, 1.0);
+            ui.add_param(&mut canvas, &mut layout, &interact, "loop end", &mut self.loop_end, 0.01, 0.0, 1.0);
+            self.loop_end = self.loop_end.max(self.loop_start);
+        }
+
+        Some(canvas.into_surface())
+    }
+}
@@ -0,0 +1,228 @@
+use crate::*;
+
+const MAX_VOICES: usize = 32;
+const KEY_WIDTH: u32 = 3;
+const KEYS_HEIGHT: u32 = 30;
+
+struct Voice {
+    region: usize,
+    key: u8,
+    position: f64,
+    step: f64,
+    level: f32,
+    released: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SoundfontPlayer {
+    path: Option<String>,
+    gain: f32,
+
+    #[serde(skip)]
+    soundfont: Soundfont,
+    #[serde(skip)]
+    voices: Vec<Voice>,
+    #[serde(skip)]
+    loader: Loader<(String, anyhow::Result<Soundfont>)>,
+}
+
+impl SoundfontPlayer {
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            gain: 0.5,
+            soundfont: Soundfont::default(),
+            voices: Vec::new(),
+            loader: Loader::default(),
+        }
+    }
+
+    pub fn load(&mut self, path: &str) -> anyhow::Result<()> {
+        self.set_soundfont(path.to_string(), Soundfont::load(path)?);
+        Ok(())
+    }
+
+    /// Parse `path` on its own thread, the instrument is swapped in by `poll`.
+    fn start_load(&mut self, path: &str) {
+        let path = path.to_string();
+        self.loader.start(move || {
+            let result = Soundfont::load(&path);
+            (path, result)
+        });
+    }
+
+    fn poll(&mut self) {
+        if let Some((path, result)) = self.loader.poll() {
+            match result {
+                Ok(soundfont) => {
+                    self.set_soundfont(path.clone(), soundfont);
+                    println!("loaded {} regions from {path}", self.soundfont.regions.len());
+                }
+                Err(err) => println!("loading {path} failed: {err}"),
+            }
+        }
+    }
+
+    fn set_soundfont(&mut self, path: String, soundfont: Soundfont) {
+        self.soundfont = soundfont;
+        self.voices.clear();
+        self.path = Some(path);
+    }
+
+    fn trigger(&mut self, note: Note) {
+        let sample_rate = get_sample_rate();
+        if sample_rate == 0 {
+            return
+        }
+
+        let pitch = freq_to_midi(note.freq());
+        let key = pitch.round().clamp(0.0, 127.0) as u8;
+
+        for (i, region) in self.soundfont.regions.iter().enumerate() {
+            if !region.matches(key, note.velocity()) {
+                continue
+            }
+
+            if MAX_VOICES <= self.voices.len() {
+                self.voices.remove(0);
+            }
+
+            let ratio = region.sample.sample_rate as f64 / sample_rate as f64;
+            self.voices.push(Voice {
+                region: i,
+                key,
+                position: region.offset as f64,
+                step: 2.0_f64.powf((pitch - region.root) as f64 / 12.0) * ratio,
+                level: 1.0,
+                released: false,
+            });
+        }
+    }
+}
+
+impl Module for SoundfontPlayer {
+    fn tick(&mut self) -> Option<Data> {
+        let sample_rate = get_sample_rate() as f32;
+        let mut value = 0.0;
+
+        for voice in self.voices.iter_mut() {
+            let region = &self.soundfont.regions[voice.region];
+
+            if voice.released {
+                voice.level -= 1.0 / (region.release * sample_rate);
+            }
+
+            value += region.sample.read(voice.position) * region.gain * voice.level.max(0.0);
+            voice.position += voice.step;
+
+            let looping = match region.loop_mode {
+                LoopMode::Continuous => true,
+                LoopMode::Sustain => !voice.released,
+                _ => false,
+            };
+            if looping && region.loop_start < region.loop_end
+                    && region.loop_end as f64 <= voice.position {
+                voice.position -= (region.loop_end - region.loop_start) as f64;
+            }
+        }
+
+        let regions = &self.soundfont.regions;
+        self.voices.retain(|voice| {
+            0.0 < voice.level && voice.position < regions[voice.region].end as f64
+        });
+
+        Some(Data::Audio(value * self.gain))
+    }
+
+    define_module! {
+        title: "Soundfont",
+        id: "soundfont",
+        output: Audio,
+        inputs: [(Notes, "notes")],
+    }
+
+    impl_serialization!();
+
+    fn loaded(&mut self) {
+        if let Some(path) = self.path.clone()
+                && let Err(err) = self.load(&path) {
+            eprintln!("loading instrument '{path}' failed: {err}");
+        }
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        // every step releases the previous notes, except one shot regions
+        for voice in self.voices.iter_mut() {
+            if self.soundfont.regions[voice.region].loop_mode != LoopMode::OneShot {
+                voice.released = true;
+            }
+        }
+
+        for note in data.notes() {
+            self.trigger(note);
+        }
+    }
+
+    fn execute(&mut self, cmd: String) {
+        let mut args = cmd.splitn(2, ' ');
+        match (args.next(), args.next()) {
+            (Some("load"), Some(path)) => self.start_load(path.trim()),
+            _ => println!("commands: load <file.sfz|file.sf2>"),
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::{Color, PixelFormatEnum},
+            rect::Rect,
+        };
+
+        self.poll();
+
+        let (width, height) = (128 * KEY_WIDTH, KEYS_HEIGHT + 90);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        // keyboard strip with the mapped and sounding keys
+        for key in 0..128 {
+            let mapped = self.soundfont.regions.iter()
+                .any(|region| region.lokey <= key && key <= region.hikey);
+            let sounding = self.voices.iter().any(|voice| voice.key == key && !voice.released);
+
+            canvas.set_draw_color(if sounding {
+                Color::RGB(200, 0, 0)
+            } else if mapped {
+                Color::RGB(120, 120, 160)
+            } else {
+                Color::RGB(230, 230, 230)
+            });
+            canvas.fill_rect(Rect::new((key as u32 * KEY_WIDTH) as i32, 0, KEY_WIDTH, KEYS_HEIGHT)).unwrap();
+        }
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_rect(Rect::new(0, 0, width, KEYS_HEIGHT)).unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, KEYS_HEIGHT as i32 + 5), mouse_pos);
+
+        let name = match &self.path {
+            _ if self.loader.is_loading() => "loading...",
+            Some(path) => path.rsplit('/').next().unwrap_or(path),
+            None => "no instrument loaded",
+        };
+        ui.add_label(&mut canvas, &mut layout, name, None);
+        layout.next_row();
+
+        ui.add_label(&mut canvas, &mut layout, &format!(
+            "regions: {} voices: {}", self.soundfont.regions.len(), self.voices.len()), None);
+        layout.next_row();
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "gain", &mut self.gain, 0.05, 0.0, 2.0);
+
+        Some(canvas.into_surface())
+    }
+}
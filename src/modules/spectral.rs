@@ -0,0 +1,311 @@
+use crate::*;
+use std::f32::consts::TAU;
+
+const FFT_SIZE: usize = 2048;
+const HOP: usize = FFT_SIZE / 4;
+const BINS: usize = FFT_SIZE / 2 + 1;
+/// Overlap-add gain of Hann analysis and synthesis windows at 4x overlap.
+const OVERLAP_GAIN: f32 = 1.5;
+const MAX_SHIFT: f32 = 128.0;
+
+const MIN_FREQ: f32 = 20.0;
+const MIN_DB: f32 = -96.0;
+const GRAPH_WIDTH: u32 = 400;
+const GRAPH_HEIGHT: u32 = 160;
+
+fn db_to_y(db: f32) -> i32 {
+    (db / MIN_DB * GRAPH_HEIGHT as f32).clamp(0.0, GRAPH_HEIGHT as f32) as i32
+}
+
+/// Phase vocoder on overlapping FFT frames. Every bin keeps track of how
+/// far its phase moves per frame, so magnitudes can be held, smeared over
+/// time or moved to other bins while the output phases keep advancing at
+/// the right rate.
+#[derive(Serialize, Deserialize)]
+pub struct Spectral {
+    /// How much of the previous frame's magnitudes is kept, 0 is no blur.
+    blur: f32,
+    /// Number of bins the spectrum is moved up or down.
+    shift: f32,
+    mix: f32,
+
+    #[serde(skip)]
+    frozen: bool,
+    #[serde(skip)]
+    frozen_magnitudes: Vec<f32>,
+    #[serde(skip)]
+    frozen_deltas: Vec<f32>,
+
+    #[serde(skip)]
+    frame: Vec<f32>,
+    #[serde(skip)]
+    fill: usize,
+    #[serde(skip)]
+    output: Vec<f32>,
+    #[serde(skip)]
+    overlap: Vec<f32>,
+    #[serde(skip)]
+    dry: DelayLine,
+    #[serde(skip)]
+    input: f32,
+
+    #[serde(skip)]
+    re: Vec<f32>,
+    #[serde(skip)]
+    im: Vec<f32>,
+    #[serde(skip)]
+    magnitudes: Vec<f32>,
+    /// Phase change of each bin since the last frame.
+    #[serde(skip)]
+    deltas: Vec<f32>,
+    #[serde(skip)]
+    last_phases: Vec<f32>,
+    #[serde(skip)]
+    blurred: Vec<f32>,
+    #[serde(skip)]
+    synth_magnitudes: Vec<f32>,
+    #[serde(skip)]
+    synth_deltas: Vec<f32>,
+    #[serde(skip)]
+    synth_phases: Vec<f32>,
+}
+
+impl Spectral {
+    pub fn new() -> Self {
+        Self {
+            blur: 0.0,
+            shift: 0.0,
+            mix: 1.0,
+            frozen: false,
+            frozen_magnitudes: Vec::new(),
+            frozen_deltas: Vec::new(),
+            frame: Vec::new(),
+            fill: 0,
+            output: Vec::new(),
+            overlap: Vec::new(),
+            dry: DelayLine::default(),
+            input: 0.0,
+            re: Vec::new(),
+            im: Vec::new(),
+            magnitudes: Vec::new(),
+            deltas: Vec::new(),
+            last_phases: Vec::new(),
+            blurred: Vec::new(),
+            synth_magnitudes: Vec::new(),
+            synth_deltas: Vec::new(),
+            synth_phases: Vec::new(),
+        }
+    }
+
+    fn allocate(&mut self) {
+        self.frame = vec![0.0; FFT_SIZE];
+        self.output = vec![0.0; HOP];
+        self.overlap = vec![0.0; FFT_SIZE];
+        self.dry = DelayLine::new(FFT_SIZE + 2);
+        self.re = vec![0.0; FFT_SIZE];
+        self.im = vec![0.0; FFT_SIZE];
+        for bins in [&mut self.magnitudes, &mut self.deltas, &mut self.last_phases, &mut self.blurred,
+                &mut self.synth_magnitudes, &mut self.synth_deltas, &mut self.synth_phases] {
+            *bins = vec![0.0; BINS];
+        }
+    }
+
+    fn toggle_freeze(&mut self) {
+        self.frozen = !self.frozen;
+        if self.frozen {
+            self.frozen_magnitudes.clone_from(&self.magnitudes);
+            self.frozen_deltas.clone_from(&self.deltas);
+        }
+    }
+
+    fn process_frame(&mut self) {
+        // analysis
+        for (i, (re, im)) in self.re.iter_mut().zip(self.im.iter_mut()).enumerate() {
+            *re = self.frame[i] * hann(i, FFT_SIZE);
+            *im = 0.0;
+        }
+        fft(&mut self.re, &mut self.im);
+
+        for bin in 0..BINS {
+            let (re, im) = (self.re[bin], self.im[bin]);
+            let phase = im.atan2(re);
+            self.magnitudes[bin] = (re * re + im * im).sqrt();
+            self.deltas[bin] = phase - self.last_phases[bin];
+            self.last_phases[bin] = phase;
+        }
+
+        let (magnitudes, deltas) = if self.frozen && self.frozen_magnitudes.len() == BINS {
+            (&self.frozen_magnitudes, &self.frozen_deltas)
+        } else {
+            (&self.magnitudes, &self.deltas)
+        };
+
+        for (blurred, magnitude) in self.blurred.iter_mut().zip(magnitudes) {
+            *blurred = *blurred * self.blur + magnitude * (1.0 - self.blur);
+        }
+
+        // a bin moved by `shift` bins turns `shift` more periods per frame
+        let shift = self.shift as isize;
+        let extra_delta = TAU * shift as f32 * HOP as f32 / FFT_SIZE as f32;
+        self.synth_magnitudes.fill(0.0);
+        self.synth_deltas.fill(0.0);
+        for (bin, (magnitude, delta)) in self.blurred.iter().zip(deltas).enumerate() {
+            let target = bin as isize + shift;
+            if 0 <= target && target < BINS as isize {
+                self.synth_magnitudes[target as usize] = *magnitude;
+                self.synth_deltas[target as usize] = delta + extra_delta;
+            }
+        }
+
+        // synthesis, as the FFT of the conjugated spectrum
+        for bin in 0..BINS {
+            let phase = (self.synth_phases[bin] + self.synth_deltas[bin]).rem_euclid(TAU);
+            self.synth_phases[bin] = phase;
+            let (sin, cos) = phase.sin_cos();
+            self.re[bin] = self.synth_magnitudes[bin] * cos;
+            self.im[bin] = -self.synth_magnitudes[bin] * sin;
+        }
+        for bin in BINS..FFT_SIZE {
+            self.re[bin] = self.re[FFT_SIZE - bin];
+            self.im[bin] = -self.im[FFT_SIZE - bin];
+        }
+        fft(&mut self.re, &mut self.im);
+
+        let gain = 1.0 / (FFT_SIZE as f32 * OVERLAP_GAIN);
+        for (i, value) in self.overlap.iter_mut().enumerate() {
+            *value += self.re[i] * hann(i, FFT_SIZE) * gain;
+        }
+        self.output.copy_from_slice(&self.overlap[..HOP]);
+        self.overlap.copy_within(HOP.., 0);
+        self.overlap[FFT_SIZE - HOP..].fill(0.0);
+    }
+
+    fn draw_graph(&self, canvas: &mut sdl2::render::SurfaceCanvas) {
+        use sdl2::{
+            pixels::Color,
+            rect::{Point, Rect},
+        };
+
+        canvas.set_draw_color(Color::RGB(230, 230, 230));
+        canvas.fill_rect(Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT)).unwrap();
+
+        let max_freq = get_sample_rate().max(1) as f32 / 2.0;
+        let bin_width = max_freq / (BINS - 1) as f32;
+        let pos_to_freq = |pos: i32| MIN_FREQ * (max_freq / MIN_FREQ).powf(pos as f32 / GRAPH_WIDTH as f32);
+        // strongest bin between two pixel columns, normalized like `windowed_spectrum`
+        let level_db = |bins: &[f32], x: i32| {
+            let start = (pos_to_freq(x) / bin_width).round() as usize;
+            let end = ((pos_to_freq(x + 1) / bin_width).round() as usize).max(start + 1).min(bins.len());
+            let magnitude = bins.get(start..end).unwrap_or_default()
+                .iter().fold(0.0_f32, |max, value| max.max(*value));
+            amplitude_to_db(magnitude * 4.0 / FFT_SIZE as f32).max(MIN_DB)
+        };
+
+        // grid lines at decades and every 12 dB
+        canvas.set_draw_color(Color::RGB(190, 190, 190));
+        for freq in [100.0, 1000.0, 10000.0] {
+            let x = ((freq / MIN_FREQ).ln() / (max_freq / MIN_FREQ).ln() * GRAPH_WIDTH as f32) as i32;
+            canvas.draw_line((x, 0), (x, GRAPH_HEIGHT as i32)).unwrap();
+        }
+        for db in (12..96).step_by(12) {
+            let y = db_to_y(-(db as f32));
+            canvas.draw_line((0, y), (GRAPH_WIDTH as i32, y)).unwrap();
+        }
+
+        if !self.synth_magnitudes.is_empty() {
+            canvas.set_draw_color(Color::RGB(150, 170, 200));
+            for x in 0..GRAPH_WIDTH as i32 {
+                let y = db_to_y(level_db(&self.synth_magnitudes, x));
+                canvas.draw_line((x, y), (x, GRAPH_HEIGHT as i32)).unwrap();
+            }
+        }
+
+        if self.frozen && !self.frozen_magnitudes.is_empty() {
+            let points: Vec<Point> = (0..GRAPH_WIDTH as i32)
+                .map(|x| Point::new(x, db_to_y(level_db(&self.frozen_magnitudes, x))))
+                .collect();
+            canvas.set_draw_color(Color::RGB(200, 0, 0));
+            canvas.draw_lines(&points[..]).unwrap();
+        }
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_rect(Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT)).unwrap();
+    }
+}
+
+impl Module for Spectral {
+    fn tick(&mut self) -> Option<Data> {
+        if self.frame.is_empty() {
+            self.allocate();
+        }
+
+        // the newest hop is collected at the end of the frame
+        self.frame[FFT_SIZE - HOP + self.fill] = self.input;
+        let wet = self.output[self.fill];
+        self.fill += 1;
+        if self.fill == HOP {
+            self.process_frame();
+            self.frame.copy_within(HOP.., 0);
+            self.fill = 0;
+        }
+
+        // delay the dry signal by the latency of the frames
+        self.dry.push(self.input);
+        let dry = self.dry.read(FFT_SIZE as f32);
+
+        Some(Data::Audio(dry + (wet - dry) * self.mix))
+    }
+
+    define_module! {
+        title: "Spectral",
+        id: "spectral",
+        output: Audio,
+        inputs: [(Audio, "input")],
+    }
+
+    impl_serialization!();
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.audio();
+    }
+
+    fn execute(&mut self, cmd: String) {
+        let args: Vec<&str> = cmd.split_whitespace().collect();
+        match args[..] {
+            ["freeze"] => self.toggle_freeze(),
+            _ => println!("commands: freeze"),
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (GRAPH_WIDTH, GRAPH_HEIGHT + 110);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        self.draw_graph(&mut canvas);
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, GRAPH_HEIGHT as i32 + 5), mouse_pos);
+
+        if ui.add_button(&mut canvas, &mut layout, &interact,
+                if self.frozen { "frozen" } else { "freeze" }, Some(6)) {
+            self.toggle_freeze();
+        }
+        layout.next_row();
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "blur", &mut self.blur, 0.05, 0.0, 0.99);
+        ui.add_param(&mut canvas, &mut layout, &interact, "shift bins", &mut self.shift, 1.0, -MAX_SHIFT, MAX_SHIFT);
+        ui.add_param(&mut canvas, &mut layout, &interact, "mix", &mut self.mix, 0.05, 0.0, 1.0);
+
+        Some(canvas.into_surface())
+    }
+}
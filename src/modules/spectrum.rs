@@ -0,0 +1,335 @@
+use crate::*;
+use std::collections::VecDeque;
+
+const FFT_SIZES: [usize; 5] = [512, 1024, 2048, 4096, 8192];
+const MIN_FREQ: f32 = 20.0;
+const MAX_FREQ: f32 = 20000.0;
+const MIN_DB: f32 = -96.0;
+/// Distance in pixels around the mouse cursor searched for a peak.
+const SEARCH_RANGE: i32 = 4;
+
+const GRAPH_WIDTH: u32 = 400;
+const GRAPH_HEIGHT: u32 = 200;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SpectrumView {
+    Spectrum,
+    Spectrogram,
+}
+
+impl SpectrumView {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Spectrum => "spectrum",
+            Self::Spectrogram => "spectrogram",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Self::Spectrum => Self::Spectrogram,
+            Self::Spectrogram => Self::Spectrum,
+        }
+    }
+}
+
+fn analyzer_buffer() -> RingBuffer {
+    RingBuffer::new(FFT_SIZES[FFT_SIZES.len() - 1] * 4)
+}
+
+fn max_freq() -> f32 {
+    MAX_FREQ.min(get_sample_rate().max(1) as f32 / 2.0)
+}
+
+/// Map a position along an axis of `size` pixels to a log frequency.
+fn pos_to_freq(pos: i32, size: u32) -> f32 {
+    MIN_FREQ * (max_freq() / MIN_FREQ).powf(pos as f32 / size as f32)
+}
+
+fn db_to_y(db: f32) -> i32 {
+    (db / MIN_DB * GRAPH_HEIGHT as f32).clamp(0.0, GRAPH_HEIGHT as f32) as i32
+}
+
+/// Map intensity 0..1 to a dark blue to yellow heat color.
+fn heat(value: f32) -> [u8; 3] {
+    let value = value.clamp(0.0, 1.0);
+    [
+        (value.sqrt() * 255.0) as u8,
+        (value * value * 255.0) as u8,
+        ((1.0 - value) * value * 4.0 * 200.0).min(255.0) as u8 + 30,
+    ]
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Spectrum {
+    fft_size: usize,
+    window: Window,
+    view: SpectrumView,
+    peak_hold: bool,
+
+    #[serde(skip)]
+    input: f32,
+    #[serde(skip, default = "analyzer_buffer")]
+    buffer: RingBuffer,
+    #[serde(skip)]
+    history: Vec<f32>,
+    /// Magnitudes of the latest FFT.
+    #[serde(skip)]
+    spectrum: Vec<f32>,
+    /// Held peak level per column in dB.
+    #[serde(skip)]
+    peaks: Vec<f32>,
+    /// Columns of the spectrogram in dB, one value per row from the top.
+    #[serde(skip)]
+    spectrogram: VecDeque<Vec<f32>>,
+}
+
+impl Spectrum {
+    pub fn new() -> Self {
+        Self {
+            fft_size: 2,
+            window: Window::Hann,
+            view: SpectrumView::Spectrum,
+            peak_hold: false,
+            input: 0.0,
+            buffer: analyzer_buffer(),
+            history: Vec::new(),
+            spectrum: Vec::new(),
+            peaks: Vec::new(),
+            spectrogram: VecDeque::new(),
+        }
+    }
+
+    fn size(&self) -> usize {
+        FFT_SIZES[self.fft_size]
+    }
+
+    fn bin_width(&self) -> f32 {
+        get_sample_rate().max(1) as f32 / self.size() as f32
+    }
+
+    /// Pull new samples from the ring buffer and redo the FFT once enough
+    /// of them arrived.
+    fn analyze(&mut self) {
+        while let Some(sample) = self.buffer.pop() {
+            self.history.push(sample);
+        }
+        let size = self.size();
+        if self.history.len() < size {
+            return
+        }
+        self.history.drain(..self.history.len() - size);
+        self.spectrum = windowed_spectrum(&self.history, self.window);
+
+        let columns: Vec<f32> = (0..GRAPH_WIDTH as i32)
+            .map(|x| self.level_db(pos_to_freq(x, GRAPH_WIDTH), pos_to_freq(x + 1, GRAPH_WIDTH)))
+            .collect();
+        if self.peak_hold && self.peaks.len() == columns.len() {
+            for (peak, level) in self.peaks.iter_mut().zip(&columns) {
+                *peak = peak.max(*level);
+            }
+        } else {
+            self.peaks = columns;
+        }
+
+        let rows = (0..GRAPH_HEIGHT as i32)
+            .map(|y| {
+                let y = GRAPH_HEIGHT as i32 - y;
+                self.level_db(pos_to_freq(y - 1, GRAPH_HEIGHT), pos_to_freq(y, GRAPH_HEIGHT))
+            })
+            .collect();
+        if GRAPH_WIDTH as usize <= self.spectrogram.len() {
+            self.spectrogram.pop_front();
+        }
+        self.spectrogram.push_back(rows);
+    }
+
+    /// Highest level in dB of the bins between two frequencies, or of the
+    /// nearest bin if the range is narrower than a bin.
+    fn level_db(&self, low: f32, high: f32) -> f32 {
+        let bin_width = self.bin_width();
+        let start = (low / bin_width).round() as usize;
+        let end = ((high / bin_width).round() as usize).max(start + 1).min(self.spectrum.len());
+        let magnitude = self.spectrum.get(start..end).unwrap_or_default()
+            .iter().fold(0.0_f32, |max, value| max.max(*value));
+        (20.0 * magnitude.max(1e-9).log10()).max(MIN_DB)
+    }
+
+    /// The strongest frequency between two frequencies, refined by fitting a
+    /// parabola through the peak bin and its neighbours.
+    fn dominant_freq(&self, low: f32, high: f32) -> Option<(f32, f32)> {
+        let bin_width = self.bin_width();
+        let start = ((low / bin_width) as usize).max(1);
+        let end = ((high / bin_width).ceil() as usize + 1).min(self.spectrum.len().saturating_sub(1));
+
+        let peak = (start..end).max_by(|a, b| self.spectrum[*a].total_cmp(&self.spectrum[*b]))?;
+        let db = |bin: usize| 20.0 * self.spectrum[bin].max(1e-9).log10();
+        let (a, b, c) = (db(peak - 1), db(peak), db(peak + 1));
+        let denominator = a - 2.0 * b + c;
+        let offset = if denominator == 0.0 { 0.0 } else { 0.5 * (a - c) / denominator };
+
+        Some(((peak as f32 + offset) * bin_width, b))
+    }
+
+    fn draw_graph(&self, canvas: &mut sdl2::render::SurfaceCanvas, cursor: Option<(i32, i32)>) {
+        use sdl2::{
+            pixels::Color,
+            rect::{Point, Rect},
+        };
+
+        canvas.set_draw_color(Color::RGB(230, 230, 230));
+        canvas.fill_rect(Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT)).unwrap();
+
+        match self.view {
+            SpectrumView::Spectrum => {
+                // grid lines at decades and every 12 dB
+                canvas.set_draw_color(Color::RGB(190, 190, 190));
+                for freq in [100.0, 1000.0, 10000.0] {
+                    let x = ((freq / MIN_FREQ).ln() / (max_freq() / MIN_FREQ).ln()
+                        * GRAPH_WIDTH as f32) as i32;
+                    canvas.draw_line((x, 0), (x, GRAPH_HEIGHT as i32)).unwrap();
+                }
+                for db in (12..96).step_by(12) {
+                    let y = db_to_y(-(db as f32));
+                    canvas.draw_line((0, y), (GRAPH_WIDTH as i32, y)).unwrap();
+                }
+
+                if !self.spectrum.is_empty() {
+                    canvas.set_draw_color(Color::RGB(150, 170, 200));
+                    for x in 0..GRAPH_WIDTH as i32 {
+                        let db = self.level_db(pos_to_freq(x, GRAPH_WIDTH), pos_to_freq(x + 1, GRAPH_WIDTH));
+                        canvas.draw_line((x, db_to_y(db)), (x, GRAPH_HEIGHT as i32)).unwrap();
+                    }
+                }
+
+                if self.peak_hold && !self.peaks.is_empty() {
+                    let points: Vec<Point> = self.peaks.iter().enumerate()
+                        .map(|(x, db)| Point::new(x as i32, db_to_y(*db)))
+                        .collect();
+                    canvas.set_draw_color(Color::RGB(200, 0, 0));
+                    canvas.draw_lines(&points[..]).unwrap();
+                }
+
+                if let Some((x, _)) = cursor {
+                    canvas.set_draw_color(Color::RGB(0, 0, 200));
+                    canvas.draw_line((x, 0), (x, GRAPH_HEIGHT as i32)).unwrap();
+                }
+            }
+            SpectrumView::Spectrogram => {
+                let offset = GRAPH_WIDTH as usize - self.spectrogram.len();
+                let surface = canvas.surface_mut();
+                let pitch = surface.pitch() as usize;
+                surface.with_lock_mut(|pixels| {
+                    for (column, rows) in self.spectrogram.iter().enumerate() {
+                        for (y, db) in rows.iter().enumerate() {
+                            let index = y * pitch + (offset + column) * 4;
+                            let [r, g, b] = heat(1.0 - db / MIN_DB);
+                            pixels[index..index + 4].copy_from_slice(&[r, g, b, 255]);
+                        }
+                    }
+                });
+
+                if let Some((_, y)) = cursor {
+                    canvas.set_draw_color(Color::RGB(255, 255, 255));
+                    canvas.draw_line((0, y), (GRAPH_WIDTH as i32, y)).unwrap();
+                }
+            }
+        }
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_rect(Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT)).unwrap();
+    }
+}
+
+impl Module for Spectrum {
+    fn tick(&mut self) -> Option<Data> {
+        // dropping samples while the window isn't drawn is fine
+        self.buffer.push(self.input);
+
+        // pass the input through so the analyzer can be put inline
+        Some(Data::Audio(self.input))
+    }
+
+    define_module! {
+        title: "Spectrum",
+        id: "spectrum",
+        output: Audio,
+        inputs: [(Audio, "audio")],
+    }
+
+    impl_serialization!();
+
+    fn loaded(&mut self) {
+        self.fft_size = self.fft_size.min(FFT_SIZES.len() - 1);
+    }
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.audio();
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (GRAPH_WIDTH, GRAPH_HEIGHT + 90);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        self.analyze();
+
+        let cursor = interact.as_ref()
+            .map(|info| (info.x as i32, info.y as i32))
+            .filter(|(x, y)| *x < GRAPH_WIDTH as i32 && *y < GRAPH_HEIGHT as i32);
+        self.draw_graph(&mut canvas, cursor);
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new(
+            (0, GRAPH_HEIGHT as i32 + 5), mouse_pos);
+
+        // frequency axis position of the cursor in the current view
+        let readout = cursor.and_then(|(x, y)| {
+            let (pos, size) = match self.view {
+                SpectrumView::Spectrum => (x, GRAPH_WIDTH),
+                SpectrumView::Spectrogram => (GRAPH_HEIGHT as i32 - y, GRAPH_HEIGHT),
+            };
+            self.dominant_freq(pos_to_freq(pos - SEARCH_RANGE, size), pos_to_freq(pos + SEARCH_RANGE, size))
+        });
+        let readout = match readout {
+            Some((freq, db)) => format!("{freq:.1} Hz, {db:.1} dB"),
+            None => "-".to_string(),
+        };
+        ui.add_label(&mut canvas, &mut layout, &readout, None);
+        layout.next_row();
+
+        ui.add_label(&mut canvas, &mut layout, self.view.as_str(), Some(12));
+        if ui.add_button(&mut canvas, &mut layout, &interact, "view", None) {
+            self.view = self.view.next();
+        }
+        let peak_hold = if self.peak_hold { "peak hold on" } else { "peak hold off" };
+        if ui.add_button(&mut canvas, &mut layout, &interact, peak_hold, None) {
+            self.peak_hold = !self.peak_hold;
+        }
+        layout.next_row();
+
+        if ui.add_button(&mut canvas, &mut layout, &interact, "<", None) {
+            self.fft_size = self.fft_size.saturating_sub(1);
+        }
+        if ui.add_button(&mut canvas, &mut layout, &interact, ">", None) {
+            self.fft_size = (self.fft_size + 1).min(FFT_SIZES.len() - 1);
+        }
+        ui.add_label(&mut canvas, &mut layout, &format!("fft {}", self.size()), Some(10));
+
+        ui.add_label(&mut canvas, &mut layout, self.window.as_str(), Some(12));
+        if ui.add_button(&mut canvas, &mut layout, &interact, "window", None) {
+            self.window = self.window.next();
+        }
+
+        Some(canvas.into_surface())
+    }
+}
@@ -0,0 +1,162 @@
+use crate::*;
+
+const WINDOW_SIZE: usize = 2048;
+const YIN_THRESHOLD: f32 = 0.15;
+/// RMS level below which the input counts as silence.
+const MIN_LEVEL: f32 = 0.01;
+/// Cents within which the note counts as in tune.
+const IN_TUNE: f32 = 3.0;
+
+const NEEDLE_WIDTH: u32 = 260;
+const NEEDLE_HEIGHT: u32 = 30;
+
+fn tuner_buffer() -> RingBuffer {
+    RingBuffer::new(WINDOW_SIZE * 4)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Tuner {
+    /// Frequency of A4 in Hz.
+    reference: f32,
+
+    #[serde(skip)]
+    input: f32,
+    #[serde(skip, default = "tuner_buffer")]
+    buffer: RingBuffer,
+    #[serde(skip)]
+    history: Vec<f32>,
+    #[serde(skip)]
+    difference: Vec<f32>,
+    #[serde(skip)]
+    freq: Option<f32>,
+}
+
+impl Tuner {
+    pub fn new() -> Self {
+        Self {
+            reference: 440.0,
+            input: 0.0,
+            buffer: tuner_buffer(),
+            history: Vec::new(),
+            difference: Vec::new(),
+            freq: None,
+        }
+    }
+
+    fn analyze(&mut self) {
+        while let Some(sample) = self.buffer.pop() {
+            self.history.push(sample);
+        }
+        if self.history.len() < WINDOW_SIZE {
+            return
+        }
+        self.history.drain(..self.history.len() - WINDOW_SIZE);
+
+        let rms = (self.history.iter().map(|value| value * value).sum::<f32>()
+            / WINDOW_SIZE as f32).sqrt();
+        self.freq = if MIN_LEVEL < rms {
+            yin(&self.history, &mut self.difference, get_sample_rate() as f32, YIN_THRESHOLD)
+        } else {
+            None
+        };
+    }
+
+    /// Nearest MIDI note and the deviation from it in cents.
+    fn note(&self, freq: f32) -> (u8, f32) {
+        let note = freq_to_midi(freq * 440.0 / self.reference);
+        let nearest = note.round().clamp(0.0, 127.0);
+        (nearest as u8, (note - nearest) * 100.0)
+    }
+
+    fn draw_needle(&self, canvas: &mut sdl2::render::SurfaceCanvas, y: i32, cents: Option<f32>) {
+        use sdl2::{
+            pixels::Color,
+            rect::Rect,
+        };
+
+        let rect = Rect::new(0, y, NEEDLE_WIDTH, NEEDLE_HEIGHT);
+        canvas.set_draw_color(Color::RGB(230, 230, 230));
+        canvas.fill_rect(rect).unwrap();
+
+        // marks every 10 cents
+        let center = NEEDLE_WIDTH as i32 / 2;
+        canvas.set_draw_color(Color::RGB(150, 150, 150));
+        for cents in (-50..=50).step_by(10) {
+            let x = center + cents * (NEEDLE_WIDTH as i32 / 2 - 1) / 50;
+            let length = if cents == 0 { NEEDLE_HEIGHT as i32 } else { NEEDLE_HEIGHT as i32 / 3 };
+            canvas.draw_line((x, y), (x, y + length)).unwrap();
+        }
+
+        if let Some(cents) = cents {
+            let x = center + (cents / 50.0 * (NEEDLE_WIDTH / 2 - 1) as f32) as i32;
+            canvas.set_draw_color(if cents.abs() < IN_TUNE {
+                Color::RGB(0, 180, 0)
+            } else {
+                Color::RGB(200, 0, 0)
+            });
+            canvas.fill_rect(Rect::new(x - 1, y, 3, NEEDLE_HEIGHT)).unwrap();
+        }
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_rect(rect).unwrap();
+    }
+}
+
+impl Module for Tuner {
+    fn tick(&mut self) -> Option<Data> {
+        // dropping samples while the window isn't drawn is fine
+        self.buffer.push(self.input);
+
+        // pass the input through so the tuner can be put inline
+        Some(Data::Audio(self.input))
+    }
+
+    define_module! {
+        title: "Tuner",
+        id: "tuner",
+        output: Audio,
+        inputs: [(Audio, "audio")],
+    }
+
+    impl_serialization!();
+
+    fn send(&mut self, _input: usize, data: Data) {
+        self.input = data.audio();
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::PixelFormatEnum,
+        };
+
+        let (width, height) = (NEEDLE_WIDTH, NEEDLE_HEIGHT + 80);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        self.analyze();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, 0), mouse_pos);
+
+        let note = self.freq.map(|freq| (freq, self.note(freq)));
+        let text = match note {
+            Some((freq, (note, cents))) => format!("{} {cents:+.0} cents, {freq:.1} Hz", note_name(note)),
+            None => "-".to_string(),
+        };
+        ui.add_label(&mut canvas, &mut layout, &text, None);
+        layout.next_row();
+
+        let (_, rect) = layout.add_rect(sdl2::rect::Rect::new(0, 0, NEEDLE_WIDTH, NEEDLE_HEIGHT));
+        self.draw_needle(&mut canvas, rect.y(), note.map(|(_, (_, cents))| cents));
+        layout.next_row();
+
+        ui.add_param(&mut canvas, &mut layout, &interact, "A4", &mut self.reference, 1.0, 415.0, 466.0);
+
+        Some(canvas.into_surface())
+    }
+}
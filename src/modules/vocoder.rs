@@ -0,0 +1,212 @@
+use crate::*;
+
+const MIN_FREQ: f32 = 100.0;
+const MAX_FREQ: f32 = 8000.0;
+const MIN_BANDS: usize = 4;
+const MAX_BANDS: usize = 32;
+
+const GRAPH_WIDTH: u32 = 320;
+const GRAPH_HEIGHT: u32 = 80;
+
+/// One pole smoothing coefficient reaching about 63% after `time` ms.
+fn coefficient(time: f32, sample_rate: f32) -> f32 {
+    1.0 - (-1000.0 / (time * sample_rate)).exp()
+}
+
+#[derive(Clone, Copy, Default)]
+struct Band {
+    /// Two bandpass filters in series for each side, for steeper slopes.
+    modulator: [Biquad; 2],
+    carrier: [Biquad; 2],
+    envelope: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Vocoder {
+    bands: usize,
+    /// Shift of the carrier bands against the modulator bands in semitones.
+    formant_shift: f32,
+    /// Envelope attack and release in milliseconds.
+    attack: f32,
+    release: f32,
+    level: f32,
+
+    #[serde(skip)]
+    carrier: f32,
+    #[serde(skip)]
+    modulator: f32,
+    /// All bands are allocated up front, only the first `active` are used.
+    #[serde(skip)]
+    filters: [Band; MAX_BANDS],
+    #[serde(skip)]
+    active: usize,
+    /// Sample rate, band count and shift the filters were designed for.
+    #[serde(skip)]
+    design: Option<(u32, usize, f32)>,
+}
+
+impl Vocoder {
+    pub fn new() -> Self {
+        Self {
+            bands: 16,
+            formant_shift: 0.0,
+            attack: 5.0,
+            release: 50.0,
+            level: 1.0,
+            carrier: 0.0,
+            modulator: 0.0,
+            filters: [Band::default(); MAX_BANDS],
+            active: 0,
+            design: None,
+        }
+    }
+
+    /// Center frequency of each of `bands` bands, spaced evenly in octaves.
+    fn band_freqs(bands: usize) -> impl Iterator<Item = f32> {
+        let ratio = MAX_FREQ / MIN_FREQ;
+        (0..bands).map(move |i| MIN_FREQ * ratio.powf((i as f32 + 0.5) / bands as f32))
+    }
+
+    fn update_filters(&mut self, sample_rate: u32) {
+        let design = Some((sample_rate, self.bands, self.formant_shift));
+        if self.design == design {
+            return
+        }
+        self.design = design;
+        // keep the filter state when only the shift changed, so it doesn't click
+        let keep_state = self.active == self.bands;
+        self.active = self.bands;
+
+        let sr = sample_rate as f32;
+        // Q for bands that meet at their edges
+        let octaves = (MAX_FREQ / MIN_FREQ).log2() / self.bands as f32;
+        let width = 2.0_f32.powf(octaves);
+        let q = width.sqrt() / (width - 1.0);
+        let shift = 2.0_f32.powf(self.formant_shift / 12.0);
+
+        let clamp = |freq: f32| freq.clamp(1.0, sr * 0.49);
+
+        for (band, freq) in self.filters[..self.active].iter_mut().zip(Self::band_freqs(self.active)) {
+            let modulator = Biquad::bandpass(clamp(freq), q, sr);
+            let carrier = Biquad::bandpass(clamp(freq * shift), q, sr);
+
+            if keep_state {
+                for filter in band.modulator.iter_mut() {
+                    filter.set_coefficients(modulator);
+                }
+                for filter in band.carrier.iter_mut() {
+                    filter.set_coefficients(carrier);
+                }
+            } else {
+                *band = Band {
+                    modulator: [modulator; 2],
+                    carrier: [carrier; 2],
+                    envelope: 0.0,
+                };
+            }
+        }
+    }
+}
+
+impl Module for Vocoder {
+    fn tick(&mut self) -> Option<Data> {
+        let sample_rate = get_sample_rate();
+        if sample_rate == 0 {
+            return Some(Data::Audio(0.0))
+        }
+        self.update_filters(sample_rate);
+
+        let attack = coefficient(self.attack, sample_rate as f32);
+        let release = coefficient(self.release, sample_rate as f32);
+
+        let mut value = 0.0;
+        for band in self.filters[..self.active].iter_mut() {
+            let modulator = band.modulator.iter_mut()
+                .fold(self.modulator, |value, filter| filter.process(value));
+            let carrier = band.carrier.iter_mut()
+                .fold(self.carrier, |value, filter| filter.process(value));
+
+            let level = modulator.abs();
+            let coefficient = if band.envelope < level { attack } else { release };
+            band.envelope += (level - band.envelope) * coefficient;
+
+            value += carrier * band.envelope;
+        }
+
+        // each band only gets a part of the power of both signals, the bands
+        // themselves add up incoherently
+        let makeup = (self.bands as f32).sqrt() * 4.0;
+        Some(Data::Audio(value * makeup * self.level))
+    }
+
+    define_module! {
+        title: "Vocoder",
+        id: "vocoder",
+        output: Audio,
+        inputs: [(Audio, "carrier"), (Audio, "modulator")],
+    }
+
+    impl_serialization!();
+
+    fn loaded(&mut self) {
+        self.bands = self.bands.clamp(MIN_BANDS, MAX_BANDS);
+    }
+
+    fn send(&mut self, input: usize, data: Data) {
+        match input {
+            0 => self.carrier = data.audio(),
+            _ => self.modulator = data.audio(),
+        }
+    }
+
+    fn draw(&mut self, ui: &UiContext, interact: Option<ModuleInteractInfo>)
+        -> Option<sdl2::surface::Surface<'_>> {
+
+        use sdl2::{
+            surface::Surface,
+            pixels::{Color, PixelFormatEnum},
+            rect::Rect,
+        };
+
+        let (width, height) = (GRAPH_WIDTH, GRAPH_HEIGHT + 130);
+
+        let mut canvas =
+            Surface::new(width, height, PixelFormatEnum::RGBA32)
+            .unwrap().into_canvas().unwrap();
+
+        // band envelopes in dB
+        canvas.set_draw_color(Color::RGB(230, 230, 230));
+        canvas.fill_rect(Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT)).unwrap();
+
+        canvas.set_draw_color(Color::RGB(0, 0, 200));
+        let count = self.active.max(1) as u32;
+        let bar_width = GRAPH_WIDTH / count;
+        for (i, band) in self.filters[..self.active].iter().enumerate() {
+            let db = amplitude_to_db(band.envelope * 2.0);
+            let fraction = ((db + 60.0) / 60.0).clamp(0.0, 1.0);
+            let bar_height = (fraction * GRAPH_HEIGHT as f32) as u32;
+            if bar_height != 0 {
+                let x = i as i32 * bar_width as i32;
+                let rect = Rect::new(x, (GRAPH_HEIGHT - bar_height) as i32, bar_width.max(2) - 1, bar_height);
+                canvas.fill_rect(rect).unwrap();
+            }
+        }
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_rect(Rect::new(0, 0, GRAPH_WIDTH, GRAPH_HEIGHT)).unwrap();
+
+        let mouse_pos = interact.as_ref().map(|info| (info.x, info.y));
+        let mut layout = crate::ui_utils::SimpleLayoutBuilder::new((0, GRAPH_HEIGHT as i32 + 5), mouse_pos);
+
+        let mut bands = self.bands as f32;
+        if ui.add_param(&mut canvas, &mut layout, &interact, "bands", &mut bands, 1.0, MIN_BANDS as f32, MAX_BANDS as f32) {
+            self.bands = bands as usize;
+        }
+        ui.add_param(&mut canvas, &mut layout, &interact, "formant shift", &mut self.formant_shift, 1.0, -12.0, 12.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "attack ms", &mut self.attack, 1.0, 1.0, 100.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "release ms", &mut self.release, 5.0, 5.0, 500.0);
+        ui.add_param(&mut canvas, &mut layout, &interact, "level", &mut self.level, 0.1, 0.0, 4.0);
+
+        Some(canvas.into_surface())
+    }
+}
@@ -3,24 +3,74 @@ use crate::*;
 use serde::{Serialize, Deserialize};
 
 use std::{
+    collections::HashMap,
     sync::{Arc, Mutex},
 };
 
+/// First byte of saved projects. Older saves start with a varint, which
+/// never begins with 255.
+const PROJECT_FORMAT: u8 = 255;
+
 #[derive(Serialize, Deserialize)]
 pub struct Project {
+    format: u8,
     pub app: App,
     pub modules: SerializeableModules,
     pub gui: Gui,
 }
 
+/// Projects saved before modules could have more than one output, when a
+/// connection only named the module it came from.
+#[derive(Deserialize)]
+struct LegacyProject {
+    app: LegacyApp,
+    modules: SerializeableModules,
+    gui: Gui,
+}
+
+#[derive(Deserialize)]
+struct LegacyApp {
+    conns: HashMap<(ModuleId, usize), ModuleId>,
+    cached: HashMap<ModuleId, Option<Data>>,
+    next_id: ModuleId,
+    selection: Option<ModuleId>,
+}
+
+impl From<LegacyProject> for Project {
+    fn from(legacy: LegacyProject) -> Self {
+        let mut app = App::new();
+        app.conns = legacy.app.conns.into_iter()
+            .map(|(input, output)| (input, (output, 0)))
+            .collect();
+        app.cached = legacy.app.cached;
+        app.next_id = legacy.app.next_id;
+        app.selection = legacy.app.selection;
+
+        Self {
+            format: PROJECT_FORMAT,
+            app,
+            modules: legacy.modules,
+            gui: legacy.gui,
+        }
+    }
+}
+
 pub fn load_file(filename: &str) -> (Arc<Mutex<App>>, Gui) {
     fn load_file(filename: &str) -> anyhow::Result<(Arc<Mutex<App>>, Gui)> {
-        let Project { mut app, gui, modules } = deserialize(std::fs::read(filename)?)?;
+        let data = std::fs::read(filename)?;
+        let project = if data.first() == Some(&PROJECT_FORMAT) {
+            deserialize(data)?
+        } else {
+            deserialize::<LegacyProject>(data)?.into()
+        };
+        let Project { mut app, gui, modules, .. } = project;
         for (id, (type_id, data)) in modules {
             let mut module = module_from_id(&type_id).unwrap();
             module.load_data(data);
             app.modules.insert(id, module);
+            app.add_level_meter(id);
         }
+        app.add_level_meter(0);
         Ok((Arc::new(Mutex::new(app)), gui))
     }
 
@@ -42,6 +92,7 @@ pub fn load_file(filename: &str) -> (Arc<Mutex<App>>, Gui) {
 
 pub fn save_file(filename: &str, app: App, modules: SerializeableModules, gui: Gui) {
     let project = Project {
+        format: PROJECT_FORMAT,
         app,
         modules,
         gui: gui.clone(),
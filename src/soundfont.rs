@@ -0,0 +1,507 @@
+use crate::*;
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{anyhow, bail};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LoopMode {
+    NoLoop,
+    OneShot,
+    Continuous,
+    Sustain,
+}
+
+/// One key/velocity zone of an instrument, playing (part of) a sample.
+#[derive(Clone)]
+pub struct Region {
+    pub sample: Arc<AudioBuffer>,
+    pub lokey: u8,
+    pub hikey: u8,
+    pub lovel: u8,
+    pub hivel: u8,
+    /// Key at which the sample plays at its original pitch, including tuning.
+    pub root: f32,
+    pub gain: f32,
+    pub offset: usize,
+    pub end: usize,
+    pub loop_mode: LoopMode,
+    pub loop_start: usize,
+    pub loop_end: usize,
+    pub release: f32,
+}
+
+impl Region {
+    fn new(sample: Arc<AudioBuffer>) -> Self {
+        let end = sample.len();
+        Self {
+            sample,
+            lokey: 0,
+            hikey: 127,
+            lovel: 0,
+            hivel: 127,
+            root: 60.0,
+            gain: 1.0,
+            offset: 0,
+            end,
+            loop_mode: LoopMode::NoLoop,
+            loop_start: 0,
+            loop_end: end,
+            release: 0.01,
+        }
+    }
+
+    pub fn matches(&self, key: u8, velocity: u8) -> bool {
+        self.lokey <= key && key <= self.hikey && self.lovel <= velocity && velocity <= self.hivel
+    }
+}
+
+/// Instrument loaded from an SFZ or SF2 file.
+#[derive(Clone, Default)]
+pub struct Soundfont {
+    pub regions: Vec<Region>,
+}
+
+impl Soundfont {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let extension = Path::new(path).extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        let soundfont = match extension.as_deref() {
+            Some("sfz") => Self::load_sfz(path)?,
+            Some("sf2") => Self::load_sf2(path)?,
+            _ => bail!("unsupported instrument format, expected .sfz or .sf2"),
+        };
+
+        if soundfont.regions.is_empty() {
+            bail!("instrument has no playable regions")
+        }
+        Ok(soundfont)
+    }
+
+    pub fn load_sfz(path: &str) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+
+        let mut default_path = String::new();
+        let mut scopes: [HashMap<String, String>; 3] = Default::default();
+        let mut regions = Vec::new();
+        let mut samples: HashMap<String, Arc<AudioBuffer>> = HashMap::new();
+
+        // index into `scopes` the current opcodes are written to,
+        // or None for <control> and the region currently being built
+        let mut scope = None;
+        let mut region: Option<HashMap<String, String>> = None;
+        let mut last_key: Option<String> = None;
+
+        let mut finish_region = |opcodes: HashMap<String, String>,
+                default_path: &str| -> anyhow::Result<()> {
+            let Some(sample) = opcodes.get("sample") else {
+                return Ok(())
+            };
+
+            let sample_path = dir.join(default_path).join(sample.replace('\\', "/"));
+            let sample_path = sample_path.to_string_lossy().to_string();
+            let buffer = match samples.get(&sample_path) {
+                Some(buffer) => Arc::clone(buffer),
+                None => {
+                    let buffer = Arc::new(AudioBuffer::load_wav(&sample_path)
+                        .map_err(|err| anyhow!("{sample_path}: {err}"))?);
+                    samples.insert(sample_path, Arc::clone(&buffer));
+                    buffer
+                }
+            };
+
+            regions.push(sfz_region(buffer, &opcodes));
+            Ok(())
+        };
+
+        for line in text.lines() {
+            let line = line.split("//").next().unwrap();
+
+            for word in line.split_whitespace() {
+                let mut word = word;
+
+                // headers may be directly followed by an opcode, as in `<region>key=60`
+                while let Some(rest) = word.strip_prefix('<') {
+                    let (header, rest) = rest.split_once('>')
+                        .ok_or_else(|| anyhow!("unterminated header: {word}"))?;
+
+                    if let Some(opcodes) = region.take() {
+                        finish_region(opcodes, &default_path)?;
+                    }
+
+                    scope = match header {
+                        "control" => None,
+                        "global" => Some(0),
+                        "master" => Some(1),
+                        "group" => Some(2),
+                        "region" => {
+                            // regions inherit everything from the enclosing headers
+                            let mut opcodes = HashMap::new();
+                            for scope in scopes.iter() {
+                                opcodes.extend(scope.clone());
+                            }
+                            region = Some(opcodes);
+                            None
+                        }
+                        _ => None,
+                    };
+
+                    // a new header resets the headers below it
+                    if let Some(scope) = scope {
+                        for inner in scopes[scope..].iter_mut() {
+                            inner.clear();
+                        }
+                    }
+
+                    last_key = None;
+                    word = rest;
+                }
+
+                if word.is_empty() {
+                    continue
+                }
+
+                let opcodes = match (&mut region, scope) {
+                    (Some(opcodes), _) => Some(opcodes),
+                    (None, Some(scope)) => Some(&mut scopes[scope]),
+                    (None, None) => None,
+                };
+
+                if let Some((key, value)) = word.split_once('=') {
+                    if key == "default_path" {
+                        default_path = value.replace('\\', "/");
+                    } else if let Some(opcodes) = opcodes {
+                        opcodes.insert(key.to_string(), value.to_string());
+                    }
+                    last_key = Some(key.to_string());
+                } else if let (Some(key), Some(opcodes)) = (&last_key, opcodes) {
+                    // values (sample paths) may contain spaces
+                    if let Some(value) = opcodes.get_mut(key) {
+                        value.push(' ');
+                        value.push_str(word);
+                    }
+                }
+            }
+        }
+
+        if let Some(opcodes) = region.take() {
+            finish_region(opcodes, &default_path)?;
+        }
+
+        Ok(Self { regions })
+    }
+
+    pub fn load_sf2(path: &str) -> anyhow::Result<Self> {
+        let data = std::fs::read(path)?;
+        Sf2::parse(&data)?.into_soundfont()
+    }
+}
+
+/// Parse a note number or name like `c#4` (where c4 is 60).
+fn parse_key(value: &str) -> Option<u8> {
+    if let Ok(key) = value.parse::<u8>() {
+        return Some(key)
+    }
+
+    let value = value.to_ascii_lowercase();
+    let mut chars = value.chars();
+    let mut key = match chars.next()? {
+        'c' => 0,
+        'd' => 2,
+        'e' => 4,
+        'f' => 5,
+        'g' => 7,
+        'a' => 9,
+        'b' => 11,
+        _ => return None,
+    };
+
+    let rest = chars.as_str();
+    let rest = if let Some(rest) = rest.strip_prefix('#') {
+        key += 1;
+        rest
+    } else if let Some(rest) = rest.strip_prefix('b') {
+        key -= 1;
+        rest
+    } else {
+        rest
+    };
+
+    let octave: i32 = rest.parse().ok()?;
+    u8::try_from((octave + 1) * 12 + key).ok()
+}
+
+fn sfz_region(sample: Arc<AudioBuffer>, opcodes: &HashMap<String, String>) -> Region {
+    let mut region = Region::new(sample);
+
+    let key = |name: &str| opcodes.get(name).and_then(|value| parse_key(value));
+    let number = |name: &str| opcodes.get(name).and_then(|value| value.parse::<f32>().ok());
+
+    if let Some(key) = key("key") {
+        region.lokey = key;
+        region.hikey = key;
+        region.root = key as f32;
+    }
+    region.lokey = key("lokey").unwrap_or(region.lokey);
+    region.hikey = key("hikey").unwrap_or(region.hikey);
+    region.lovel = number("lovel").map_or(region.lovel, |vel| vel as u8);
+    region.hivel = number("hivel").map_or(region.hivel, |vel| vel as u8);
+
+    region.root = key("pitch_keycenter").map_or(region.root, |key| key as f32);
+    region.root -= number("transpose").unwrap_or(0.0) + number("tune").unwrap_or(0.0) / 100.0;
+    region.gain = 10.0_f32.powf(number("volume").unwrap_or(0.0) / 20.0);
+
+    let len = region.sample.len();
+    region.offset = number("offset").map_or(0, |offset| offset as usize).min(len);
+    region.end = number("end").map_or(len, |end| end as usize + 1).min(len);
+
+    region.loop_mode = match opcodes.get("loop_mode").or(opcodes.get("loopmode")).map(String::as_str) {
+        Some("one_shot") => LoopMode::OneShot,
+        Some("loop_continuous") => LoopMode::Continuous,
+        Some("loop_sustain") => LoopMode::Sustain,
+        _ => LoopMode::NoLoop,
+    };
+    region.loop_start = number("loop_start").or(number("loopstart"))
+        .map_or(region.offset, |start| start as usize);
+    region.loop_end = number("loop_end").or(number("loopend"))
+        .map_or(region.end, |end| end as usize + 1).min(region.end);
+
+    region.release = number("ampeg_release").unwrap_or(region.release).max(0.005);
+
+    region
+}
+
+// SF2 generator operators
+const GEN_START_OFFSET: u16 = 0;
+const GEN_END_OFFSET: u16 = 1;
+const GEN_START_LOOP_OFFSET: u16 = 2;
+const GEN_END_LOOP_OFFSET: u16 = 3;
+const GEN_RELEASE_VOL_ENV: u16 = 38;
+const GEN_INSTRUMENT: u16 = 41;
+const GEN_KEY_RANGE: u16 = 43;
+const GEN_VEL_RANGE: u16 = 44;
+const GEN_INITIAL_ATTENUATION: u16 = 48;
+const GEN_COARSE_TUNE: u16 = 51;
+const GEN_FINE_TUNE: u16 = 52;
+const GEN_SAMPLE_ID: u16 = 53;
+const GEN_SAMPLE_MODES: u16 = 54;
+const GEN_OVERRIDING_ROOT_KEY: u16 = 58;
+
+struct Sf2SampleHeader {
+    start: usize,
+    end: usize,
+    loop_start: usize,
+    loop_end: usize,
+    sample_rate: u32,
+    original_pitch: u8,
+    pitch_correction: i8,
+}
+
+type Generators = HashMap<u16, [u8; 2]>;
+
+/// The parts of an SF2 file needed to play its first preset.
+struct Sf2 {
+    samples: Vec<i16>,
+    headers: Vec<Sf2SampleHeader>,
+    // zones of the first preset and of every instrument
+    preset_zones: Vec<Generators>,
+    instrument_zones: Vec<Vec<Generators>>,
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+/// Iterate over the (id, data) RIFF sub chunks in `data`.
+fn riff_chunks(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        if data.len() < 8 {
+            return None
+        }
+        let id = data[0..4].try_into().unwrap();
+        let size = (u32_at(data, 4) as usize).min(data.len() - 8);
+        let chunk = &data[8..8 + size];
+        data = &data[(8 + size + size % 2).min(data.len())..];
+        Some((id, chunk))
+    })
+}
+
+/// Split the bag/generator lists into a list of generator maps per zone,
+/// for the zones `bag_start..bag_end`.
+fn sf2_zones(bags: &[u8], gens: &[u8], bag_start: usize, bag_end: usize) -> Vec<Generators> {
+    (bag_start..bag_end).filter_map(|bag| {
+        if bags.len() < (bag + 2) * 4 {
+            return None
+        }
+        let gen_start = u16_at(bags, bag * 4) as usize;
+        let gen_end = u16_at(bags, (bag + 1) * 4) as usize;
+        Some((gen_start..gen_end)
+            .filter(|index| (index + 1) * 4 <= gens.len())
+            .map(|index| (u16_at(gens, index * 4), [gens[index * 4 + 2], gens[index * 4 + 3]]))
+            .collect())
+    }).collect()
+}
+
+fn gen_i16(gens: &Generators, op: u16) -> Option<i16> {
+    gens.get(&op).map(|amount| i16::from_le_bytes(*amount))
+}
+
+impl Sf2 {
+    fn parse(data: &[u8]) -> anyhow::Result<Self> {
+        if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"sfbk" {
+            bail!("not an SF2 file")
+        }
+
+        let mut samples = Vec::new();
+        let mut pdta: HashMap<[u8; 4], &[u8]> = HashMap::new();
+
+        for (id, chunk) in riff_chunks(&data[12..]) {
+            if &id != b"LIST" || chunk.len() < 4 {
+                continue
+            }
+            for (sub_id, sub_chunk) in riff_chunks(&chunk[4..]) {
+                match &chunk[0..4] {
+                    b"sdta" if &sub_id == b"smpl" => {
+                        samples = sub_chunk.chunks_exact(2)
+                            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+                            .collect();
+                    }
+                    b"pdta" => { pdta.insert(sub_id, sub_chunk); }
+                    _ => {}
+                }
+            }
+        }
+
+        let chunk = |id: &[u8; 4]| pdta.get(id).copied()
+            .ok_or_else(|| anyhow!("missing {} chunk", String::from_utf8_lossy(id)));
+
+        let (phdr, pbag, pgen) = (chunk(b"phdr")?, chunk(b"pbag")?, chunk(b"pgen")?);
+        let (inst, ibag, igen) = (chunk(b"inst")?, chunk(b"ibag")?, chunk(b"igen")?);
+        let shdr = chunk(b"shdr")?;
+
+        // the last record of every list is a terminal record
+        if phdr.len() < 38 * 2 {
+            bail!("SF2 file has no presets")
+        }
+        let preset_zones = sf2_zones(pbag, pgen, u16_at(phdr, 24) as usize, u16_at(phdr, 38 + 24) as usize);
+
+        let instrument_zones = (0..(inst.len() / 22).saturating_sub(1)).map(|i| {
+            sf2_zones(ibag, igen, u16_at(inst, i * 22 + 20) as usize, u16_at(inst, (i + 1) * 22 + 20) as usize)
+        }).collect();
+
+        let headers = shdr.chunks_exact(46).map(|header| Sf2SampleHeader {
+            start: u32_at(header, 20) as usize,
+            end: u32_at(header, 24) as usize,
+            loop_start: u32_at(header, 28) as usize,
+            loop_end: u32_at(header, 32) as usize,
+            sample_rate: u32_at(header, 36),
+            original_pitch: header[40],
+            pitch_correction: header[41] as i8,
+        }).collect();
+
+        Ok(Self { samples, headers, preset_zones, instrument_zones })
+    }
+
+    fn into_soundfont(self) -> anyhow::Result<Soundfont> {
+        let mut regions = Vec::new();
+        let mut buffers: HashMap<usize, Arc<AudioBuffer>> = HashMap::new();
+
+        for preset_zone in self.preset_zones.iter() {
+            let Some(instrument) = gen_i16(preset_zone, GEN_INSTRUMENT) else {
+                continue
+            };
+            let Some(zones) = self.instrument_zones.get(instrument as usize) else {
+                continue
+            };
+
+            // a first zone without a sample holds the defaults of the instrument
+            let global = zones.first()
+                .filter(|zone| !zone.contains_key(&GEN_SAMPLE_ID))
+                .cloned()
+                .unwrap_or_default();
+
+            for zone in zones.iter().filter(|zone| zone.contains_key(&GEN_SAMPLE_ID)) {
+                let mut gens = global.clone();
+                gens.extend(zone.iter());
+
+                let sample_id = gen_i16(&gens, GEN_SAMPLE_ID).unwrap() as u16 as usize;
+                let Some(header) = self.headers.get(sample_id) else {
+                    continue
+                };
+
+                let buffer = buffers.entry(sample_id).or_insert_with(|| {
+                    let end = header.end.min(self.samples.len());
+                    Arc::new(AudioBuffer {
+                        samples: self.samples[header.start.min(end)..end].iter()
+                            .map(|sample| *sample as f32 / 32768.0)
+                            .collect(),
+                        sample_rate: header.sample_rate,
+                    })
+                });
+
+                let mut region = Region::new(Arc::clone(buffer));
+                let len = region.sample.len() as i64;
+                let offset = |op: u16| gen_i16(&gens, op).unwrap_or(0) as i64;
+                let clamp = |value: i64| value.clamp(0, len) as usize;
+
+                // intersect the preset and instrument ranges
+                for (op, lo, hi) in [
+                    (GEN_KEY_RANGE, &mut region.lokey, &mut region.hikey),
+                    (GEN_VEL_RANGE, &mut region.lovel, &mut region.hivel),
+                ] {
+                    for range in [preset_zone.get(&op), gens.get(&op)].into_iter().flatten() {
+                        *lo = (*lo).max(range[0]);
+                        *hi = (*hi).min(range[1]);
+                    }
+                }
+
+                let root = match gen_i16(&gens, GEN_OVERRIDING_ROOT_KEY) {
+                    Some(key) if (0..128).contains(&key) => key as f32,
+                    _ => header.original_pitch as f32,
+                };
+                // preset generators are added on top of the instrument ones
+                let preset_offset = |op: u16| gen_i16(preset_zone, op).unwrap_or(0) as i64;
+                let coarse = offset(GEN_COARSE_TUNE) + preset_offset(GEN_COARSE_TUNE);
+                let fine = offset(GEN_FINE_TUNE) + preset_offset(GEN_FINE_TUNE)
+                    + header.pitch_correction as i64;
+                let tune = coarse as f32 + fine as f32 / 100.0;
+                region.root = root - tune;
+
+                // attenuation is in centibels
+                region.gain = 10.0_f32.powf(-(offset(GEN_INITIAL_ATTENUATION) as f32) / 200.0);
+
+                region.offset = clamp(offset(GEN_START_OFFSET));
+                region.end = clamp(len + offset(GEN_END_OFFSET));
+                region.loop_start = clamp(header.loop_start as i64 - header.start as i64
+                    + offset(GEN_START_LOOP_OFFSET));
+                region.loop_end = clamp(header.loop_end as i64 - header.start as i64
+                    + offset(GEN_END_LOOP_OFFSET));
+                region.loop_mode = match gen_i16(&gens, GEN_SAMPLE_MODES).unwrap_or(0) & 3 {
+                    1 => LoopMode::Continuous,
+                    3 => LoopMode::Sustain,
+                    _ => LoopMode::NoLoop,
+                };
+
+                // release is in timecents
+                if let Some(release) = gen_i16(&gens, GEN_RELEASE_VOL_ENV) {
+                    region.release = 2.0_f32.powf(release as f32 / 1200.0).max(0.005);
+                }
+
+                regions.push(region);
+            }
+        }
+
+        Ok(Soundfont { regions })
+    }
+}
@@ -3,3 +3,6 @@ pub use context::UiContext;
 
 mod layout;
 pub use layout::SimpleLayoutBuilder;
+
+mod waveform;
+pub use waveform::draw_waveform;
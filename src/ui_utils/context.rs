@@ -92,4 +92,38 @@ impl UiContext<'_> {
         canvas.draw_rect(rect).unwrap();
         hovered && clicked
     }
+
+    /// Label showing `value` followed by "-" and "+" buttons stepping it
+    /// within `min..=max`. Returns true if the value was changed.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_param(
+            &self,
+            canvas: &mut SurfaceCanvas,
+            layout: &mut super::SimpleLayoutBuilder,
+            interact: &Option<ModuleInteractInfo>,
+            label: &str,
+            value: &mut f32,
+            step: f32,
+            min: f32,
+            max: f32) -> bool {
+
+        let old = *value;
+
+        let text = if step.fract() == 0.0 {
+            format!("{label}: {:.0}", *value)
+        } else {
+            format!("{label}: {:.2}", *value)
+        };
+
+        self.add_label(canvas, layout, &text, Some(16));
+        if self.add_button(canvas, layout, interact, "-", None) {
+            *value = f32::max(min, *value - step);
+        }
+        if self.add_button(canvas, layout, interact, "+", None) {
+            *value = f32::min(max, *value + step);
+        }
+        layout.next_row();
+
+        old != *value
+    }
 }
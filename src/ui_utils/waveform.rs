@@ -0,0 +1,29 @@
+use sdl2::{
+    render::SurfaceCanvas,
+    rect::Rect,
+};
+
+/// Draw `samples` squeezed into `rect`, one vertical min/max line per column,
+/// using the current draw color.
+pub fn draw_waveform(canvas: &mut SurfaceCanvas, rect: Rect, samples: &[f32]) {
+    if samples.is_empty() {
+        return
+    }
+
+    let width = rect.width() as usize;
+    let half = rect.height() as f32 / 2.0;
+    let center = rect.y() + half as i32;
+
+    for x in 0..width {
+        let start = x * samples.len() / width;
+        let end = ((x + 1) * samples.len() / width).max(start + 1).min(samples.len());
+
+        let (min, max) = samples[start.min(samples.len() - 1)..end].iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), sample| (min.min(*sample), max.max(*sample)));
+
+        let x = rect.x() + x as i32;
+        let top = center - (max.clamp(-1.0, 1.0) * half) as i32;
+        let bottom = center - (min.clamp(-1.0, 1.0) * half) as i32;
+        canvas.draw_line((x, top), (x, bottom)).unwrap();
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WavFormat {
+    Int,
+    Float,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WavSpec {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits: u16,
+    pub format: WavFormat,
+}
+
+impl WavSpec {
+    fn frame_size(&self) -> usize {
+        self.channels as usize * self.bits as usize / 8
+    }
+}
+
+/// Streaming reader for PCM and float WAV files, decoding to f32.
+pub struct WavReader {
+    reader: BufReader<File>,
+    pub spec: WavSpec,
+    data_start: u64,
+    frames: usize,
+    position: usize,
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+impl WavReader {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut header = [0; 12];
+        reader.read_exact(&mut header)?;
+        if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+            bail!("not a WAV file")
+        }
+
+        let mut spec = None;
+
+        loop {
+            let mut chunk = [0; 8];
+            reader.read_exact(&mut chunk)?;
+            let size = read_u32(&chunk[4..8]) as u64;
+
+            match &chunk[0..4] {
+                b"fmt " => {
+                    let mut fmt = vec![0; size as usize];
+                    reader.read_exact(&mut fmt)?;
+                    if fmt.len() < 16 {
+                        bail!("invalid fmt chunk")
+                    }
+
+                    let mut tag = read_u16(&fmt[0..2]);
+                    // WAVE_FORMAT_EXTENSIBLE stores the real format in the sub format GUID
+                    if tag == 0xFFFE && 26 <= fmt.len() {
+                        tag = read_u16(&fmt[24..26]);
+                    }
+
+                    let format = match tag {
+                        1 => WavFormat::Int,
+                        3 => WavFormat::Float,
+                        _ => bail!("unsupported WAV format tag: {tag}"),
+                    };
+
+                    let bits = read_u16(&fmt[14..16]);
+                    match (format, bits) {
+                        (WavFormat::Int, 8 | 16 | 24 | 32) | (WavFormat::Float, 32 | 64) => {}
+                        _ => bail!("unsupported bit depth: {bits}"),
+                    }
+
+                    spec = Some(WavSpec {
+                        channels: read_u16(&fmt[2..4]),
+                        sample_rate: read_u32(&fmt[4..8]),
+                        bits,
+                        format,
+                    });
+                }
+                b"data" => {
+                    let spec = spec.ok_or_else(|| anyhow!("data chunk before fmt chunk"))?;
+                    if spec.channels == 0 {
+                        bail!("WAV file has no channels")
+                    }
+                    let data_start = reader.stream_position()?;
+                    return Ok(Self {
+                        reader,
+                        spec,
+                        data_start,
+                        frames: size as usize / spec.frame_size(),
+                        position: 0,
+                    })
+                }
+                _ => {
+                    // chunks are padded to an even size
+                    reader.seek_relative((size + size % 2) as i64)?;
+                }
+            }
+        }
+    }
+
+    /// Length in frames (one sample per channel).
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn seek(&mut self, frame: usize) -> anyhow::Result<()> {
+        let frame = frame.min(self.frames);
+        let offset = self.data_start + (frame * self.spec.frame_size()) as u64;
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.position = frame;
+        Ok(())
+    }
+
+    /// Read up to `frames` frames of interleaved samples into `output`,
+    /// returning the amount of frames read.
+    pub fn read_frames(&mut self, output: &mut Vec<f32>, frames: usize) -> anyhow::Result<usize> {
+        let frames = frames.min(self.frames - self.position);
+        let bytes_per_sample = self.spec.bits as usize / 8;
+
+        let mut buffer = vec![0; frames * self.spec.frame_size()];
+        self.reader.read_exact(&mut buffer)?;
+        self.position += frames;
+
+        output.extend(buffer.chunks_exact(bytes_per_sample).map(|bytes| {
+            match (self.spec.format, self.spec.bits) {
+                (WavFormat::Int, 8) => (bytes[0] as f32 - 128.0) / 128.0,
+                (WavFormat::Int, 16) => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+                (WavFormat::Int, 24) =>
+                    (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as f32 / 8388608.0,
+                (WavFormat::Int, _) =>
+                    i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 2147483648.0,
+                (WavFormat::Float, 32) => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+                (WavFormat::Float, _) => f64::from_le_bytes(bytes.try_into().unwrap()) as f32,
+            }
+        }));
+
+        Ok(frames)
+    }
+}
+
+/// Writer for mono 32 bit float WAV files. The header is completed by
+/// `finalize`, files that are never finalized have a zero data length.
+pub struct WavWriter {
+    writer: BufWriter<File>,
+    sample_rate: u32,
+    frames: u32,
+}
+
+impl WavWriter {
+    pub fn create(path: impl AsRef<Path>, sample_rate: u32) -> anyhow::Result<Self> {
+        let mut writer = Self {
+            writer: BufWriter::new(File::create(path)?),
+            sample_rate,
+            frames: 0,
+        };
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    fn write_header(&mut self) -> anyhow::Result<()> {
+        let data_size = self.frames * 4;
+        let writer = &mut self.writer;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(36 + data_size).to_le_bytes())?;
+        writer.write_all(b"WAVEfmt ")?;
+        writer.write_all(&16_u32.to_le_bytes())?;
+        writer.write_all(&3_u16.to_le_bytes())?; // IEEE float
+        writer.write_all(&1_u16.to_le_bytes())?; // channels
+        writer.write_all(&self.sample_rate.to_le_bytes())?;
+        writer.write_all(&(self.sample_rate * 4).to_le_bytes())?; // bytes per second
+        writer.write_all(&4_u16.to_le_bytes())?; // bytes per frame
+        writer.write_all(&32_u16.to_le_bytes())?; // bits per sample
+        writer.write_all(b"data")?;
+        writer.write_all(&data_size.to_le_bytes())?;
+        Ok(())
+    }
+
+    pub fn write_samples(&mut self, samples: &[f32]) -> anyhow::Result<()> {
+        for sample in samples {
+            self.writer.write_all(&sample.to_le_bytes())?;
+        }
+        self.frames += samples.len() as u32;
+        Ok(())
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Fill in the sizes in the header and flush the file.
+    pub fn finalize(mut self) -> anyhow::Result<()> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A fully loaded audio file, mixed down to mono.
+#[derive(Clone, Default)]
+pub struct AudioBuffer {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+}
+
+impl AudioBuffer {
+    pub fn load_wav(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut reader = WavReader::open(path)?;
+        let channels = reader.spec.channels as usize;
+
+        let mut interleaved = Vec::new();
+        reader.read_frames(&mut interleaved, reader.frames())?;
+
+        Ok(Self {
+            samples: interleaved.chunks_exact(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+                .collect(),
+            sample_rate: reader.spec.sample_rate,
+        })
+    }
+
+    /// Load a WAV file along with a copy resampled to `sample_rate`, which
+    /// stays empty while the sample rate is not known.
+    pub fn load_wav_resampled(path: impl AsRef<Path>, sample_rate: u32) -> anyhow::Result<(Self, Self)> {
+        let source = Self::load_wav(path)?;
+        let sample = if sample_rate != 0 {
+            source.resampled(sample_rate)
+        } else {
+            Self::default()
+        };
+        Ok((source, sample))
+    }
+
+    /// Resample to `sample_rate` using linear interpolation.
+    pub fn resampled(&self, sample_rate: u32) -> Self {
+        if sample_rate == self.sample_rate || self.samples.is_empty() {
+            return Self {
+                samples: self.samples.clone(),
+                sample_rate,
+            }
+        }
+
+        let ratio = self.sample_rate as f64 / sample_rate as f64;
+        let len = (self.samples.len() as f64 / ratio) as usize;
+        let last = self.samples.len() - 1;
+
+        Self {
+            samples: (0..len).map(|i| {
+                let position = i as f64 * ratio;
+                let index = position as usize;
+                let frac = (position - index as f64) as f32;
+                let a = self.samples[index.min(last)];
+                let b = self.samples[(index + 1).min(last)];
+                a + (b - a) * frac
+            }).collect(),
+            sample_rate,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Linearly interpolated read at a fractional position.
+    pub fn read(&self, position: f64) -> f32 {
+        let index = position as usize;
+        if self.samples.len() <= index + 1 {
+            return self.samples.last().copied().unwrap_or(0.0)
+        }
+        let frac = (position - index as f64) as f32;
+        let a = self.samples[index];
+        a + (self.samples[index + 1] - a) * frac
+    }
+}